@@ -0,0 +1,278 @@
+//! Texture atlas animation driven by sprite sheet files.
+//!
+//! A `*.sheet.json` file names the image, relative to the assets folder, how
+//! it is cut into frames and which frames make up each clip:
+//!
+//! ```json
+//! {
+//!     "image": "patron.png",
+//!     "tile_size": [209, 209],
+//!     "columns": 4,
+//!     "rows": 1,
+//!     "clips": {
+//!         "idle": { "frames": [0, 1], "fps": 4 },
+//!         "thrusting": { "frames": [2, 3], "fps": 12 },
+//!         "dying": { "frames": [0], "fps": 4, "looping": false }
+//!     }
+//! }
+//! ```
+//!
+//! Missing clips fall back to `idle`, so a sheet with a single frame works
+//! for anything. Sheets without `idle` or with frames past the grid are
+//! rejected when loading.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::GameTime;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Clip {
+    Idle,
+    Thrusting,
+    Damaged,
+    Dying,
+}
+
+#[derive(Deserialize)]
+struct ClipDef {
+    frames: Vec<usize>,
+    fps: f32,
+    #[serde(default = "looping_by_default")]
+    looping: bool,
+}
+
+fn looping_by_default() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct SheetFile {
+    image: String,
+    tile_size: Vec2,
+    columns: usize,
+    rows: usize,
+    clips: HashMap<Clip, ClipDef>,
+}
+
+#[derive(TypeUuid)]
+#[uuid = "5f0d7c7e-2b1a-4f53-9a64-8d2f3e6b1c49"]
+pub struct SpriteSheet {
+    image: Handle<Image>,
+    atlas: Handle<TextureAtlas>,
+    clips: HashMap<Clip, ClipDef>,
+}
+
+impl SpriteSheet {
+    /// Image the frames are cut from.
+    pub fn image(&self) -> &Handle<Image> {
+        &self.image
+    }
+
+    fn has(&self, clip: Clip) -> bool {
+        self.clips.contains_key(&clip)
+    }
+
+    fn clip(&self, clip: Clip) -> Option<&ClipDef> {
+        self.clips
+            .get(&clip)
+            .or_else(|| self.clips.get(&Clip::Idle))
+    }
+}
+
+/// Rejects sheets that [`animate_sprites`] could not play: clips fall back on
+/// `idle`, and every frame has to lie inside the grid.
+fn validate(file: &SheetFile) -> Result<(), bevy::asset::Error> {
+    if !file.clips.contains_key(&Clip::Idle) {
+        return Err(bevy::asset::Error::msg("sprite sheet has no idle clip"));
+    }
+    let count = file.columns * file.rows;
+    if let Some(frame) = file
+        .clips
+        .values()
+        .flat_map(|clip| clip.frames.iter())
+        .find(|frame| **frame >= count)
+    {
+        return Err(bevy::asset::Error::msg(format!(
+            "sprite sheet frame {frame} is past its {count} frames"
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct SpriteSheetLoader;
+
+impl AssetLoader for SpriteSheetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let file: SheetFile = serde_json::from_slice(bytes)?;
+            validate(&file)?;
+            let image_path = AssetPath::new(PathBuf::from(&file.image), None);
+            let image = load_context.get_handle(image_path.clone());
+            let atlas =
+                TextureAtlas::from_grid(image.clone(), file.tile_size, file.columns, file.rows);
+            load_context.set_labeled_asset("atlas", LoadedAsset::new(atlas));
+            let sheet = SpriteSheet {
+                image,
+                atlas: load_context
+                    .get_handle(AssetPath::new_ref(load_context.path(), Some("atlas"))),
+                clips: file.clips,
+            };
+            load_context.set_default_asset(LoadedAsset::new(sheet).with_dependency(image_path));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sheet.json"]
+    }
+}
+
+/// Plays clips of a [`SpriteSheet`] on a [`TextureAtlasSprite`].
+#[derive(Component)]
+pub struct Animation {
+    sheet: Handle<SpriteSheet>,
+    clip: Clip,
+    /// Position in the clip frames.
+    frame: usize,
+    /// Seconds spent on the current frame.
+    elapsed: f32,
+    finished: bool,
+    /// The sheet lacks the clip and plays `idle` instead.
+    stand_in: bool,
+}
+
+impl Animation {
+    pub fn new(sheet: Handle<SpriteSheet>) -> Self {
+        Animation {
+            sheet,
+            clip: Clip::Idle,
+            frame: 0,
+            elapsed: 0.,
+            finished: false,
+            stand_in: false,
+        }
+    }
+
+    /// Switches to `clip`, from its first frame unless it is already playing.
+    pub fn play(&mut self, clip: Clip) {
+        if self.clip != clip {
+            self.clip = clip;
+            self.frame = 0;
+            self.elapsed = 0.;
+            self.finished = false;
+        }
+    }
+
+    /// Whether a clip that does not loop has shown its last frame. A clip
+    /// missing from the sheet counts as finished, the `idle` playing in its
+    /// place never ends.
+    pub fn finished(&self) -> bool {
+        self.finished || self.stand_in
+    }
+}
+
+/// Despawns the entity once its current clip is over, for wrecks playing
+/// their dying frames.
+#[derive(Component)]
+pub struct DespawnAfterClip;
+
+pub fn animate_sprites(
+    time: Res<GameTime>,
+    sheets: Res<Assets<SpriteSheet>>,
+    mut sprites: Query<(
+        &mut Animation,
+        &mut TextureAtlasSprite,
+        &mut Handle<TextureAtlas>,
+    )>,
+) {
+    for (mut animation, mut sprite, mut atlas) in &mut sprites {
+        let sheet = match sheets.get(&animation.sheet) {
+            Some(sheet) => sheet,
+            None => continue,
+        };
+        if *atlas != sheet.atlas {
+            *atlas = sheet.atlas.clone();
+        }
+        animation.stand_in = !sheet.has(animation.clip);
+        let clip = match sheet.clip(animation.clip) {
+            Some(clip) if !clip.frames.is_empty() => clip,
+            _ => continue,
+        };
+
+        animation.elapsed += time.delta_seconds();
+        let frame_time = 1. / clip.fps.max(f32::EPSILON);
+        while animation.elapsed >= frame_time && !animation.finished {
+            animation.elapsed -= frame_time;
+            if animation.frame + 1 < clip.frames.len() {
+                animation.frame += 1;
+            } else if clip.looping {
+                animation.frame = 0;
+            } else {
+                animation.finished = true;
+            }
+        }
+        let index = clip.frames[animation.frame.min(clip.frames.len() - 1)];
+        if sprite.index != index {
+            sprite.index = index;
+        }
+    }
+}
+
+pub fn despawn_finished(
+    mut commands: Commands,
+    animations: Query<(Entity, &Animation), With<DespawnAfterClip>>,
+) {
+    for (entity, animation) in &animations {
+        if animation.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(clips: &str) -> SheetFile {
+        serde_json::from_str(&format!(
+            r#"{{ "image": "ship.png", "tile_size": [32, 32], "columns": 2, "rows": 2, "clips": {{ {clips} }} }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn accepts_frames_inside_the_grid() {
+        let file = sheet(r#""idle": { "frames": [0, 3], "fps": 4 }"#);
+        assert!(validate(&file).is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_idle() {
+        let file = sheet(r#""thrusting": { "frames": [0, 1], "fps": 12 }"#);
+        let error = validate(&file).unwrap_err();
+        assert!(error.to_string().contains("no idle clip"));
+    }
+
+    #[test]
+    fn rejects_frame_past_the_grid() {
+        let file = sheet(
+            r#""idle": { "frames": [0], "fps": 4 }, "dying": { "frames": [2, 4], "fps": 4 }"#,
+        );
+        let error = validate(&file).unwrap_err();
+        assert!(error.to_string().contains("frame 4 is past its 4 frames"));
+    }
+}
@@ -0,0 +1,63 @@
+//! Typed handles to everything the game draws, writes with and plays,
+//! requested once at startup so spawning never goes through the asset server.
+
+use std::collections::HashMap;
+
+use bevy::{asset::HandleId, prelude::*};
+
+use crate::{animation::SpriteSheet, audio::Sounds, EnemyKind};
+
+pub struct GameAssets {
+    pub font: Handle<Font>,
+    pub background: Handle<Image>,
+    pub bullet: Handle<Image>,
+    pub ship: Handle<SpriteSheet>,
+    enemies: HashMap<EnemyKind, Handle<SpriteSheet>>,
+    pub boss_core: Handle<Image>,
+    pub boss_turret: Handle<Image>,
+    pub sounds: Sounds,
+}
+
+impl FromWorld for GameAssets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        GameAssets {
+            font: asset_server.load("FiraSans-Bold.ttf"),
+            background: asset_server.load("bg.png"),
+            bullet: asset_server.load("bullet.png"),
+            ship: asset_server.load("sheets/patron.sheet.json"),
+            enemies: EnemyKind::ALL
+                .into_iter()
+                .map(|kind| (kind, asset_server.load(kind.def().sheet)))
+                .collect(),
+            boss_core: asset_server.load("enemy-big.png"),
+            boss_turret: asset_server.load("enemy-medium.png"),
+            sounds: Sounds::load(asset_server),
+        }
+    }
+}
+
+impl GameAssets {
+    pub fn enemy(&self, kind: EnemyKind) -> Handle<SpriteSheet> {
+        self.enemies[&kind].clone()
+    }
+
+    /// Sprite sheets, whose images are only known once the sheet is loaded.
+    pub fn sheets(&self) -> impl Iterator<Item = &Handle<SpriteSheet>> {
+        std::iter::once(&self.ship).chain(self.enemies.values())
+    }
+
+    /// Every handle, for the loading screen to wait on.
+    pub fn handles(&self) -> Vec<HandleId> {
+        let mut handles = vec![
+            self.font.id,
+            self.background.id,
+            self.bullet.id,
+            self.boss_core.id,
+            self.boss_turret.id,
+        ];
+        handles.extend(self.sheets().map(|h| h.id));
+        handles.extend(self.sounds.handles());
+        handles
+    }
+}
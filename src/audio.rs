@@ -0,0 +1,220 @@
+//! Sound effects and background music, mixed on the two volume channels of
+//! the settings.
+//!
+//! Gameplay systems send [`PlaySfx`] events instead of touching the audio
+//! output, and the music follows the app state on its own. Browsers keep
+//! audio suspended until the page is clicked or a key is pressed, which
+//! `static/restart-audio-context.js` takes care of on the web build.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::{asset::HandleId, audio::AudioSink, prelude::*};
+
+use crate::{
+    any_gamepad_just_pressed,
+    assets::GameAssets,
+    controls::{Action, Actions},
+    focus::Focused,
+    settings::{Channel, Settings},
+    AppState,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sfx {
+    Fire,
+    Split,
+    Explode,
+    PlayerDeath,
+    Click,
+}
+
+impl Sfx {
+    const ALL: [Sfx; 5] = [
+        Sfx::Fire,
+        Sfx::Split,
+        Sfx::Explode,
+        Sfx::PlayerDeath,
+        Sfx::Click,
+    ];
+
+    fn path(self) -> &'static str {
+        match self {
+            Sfx::Fire => "sounds/fire.wav",
+            Sfx::Split => "sounds/split.wav",
+            Sfx::Explode => "sounds/explode.wav",
+            Sfx::PlayerDeath => "sounds/player_death.wav",
+            Sfx::Click => "sounds/click.wav",
+        }
+    }
+}
+
+/// Asks for a sound effect to be played at the effects volume.
+pub struct PlaySfx(pub Sfx);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Track {
+    Menu,
+    Game,
+    GameOver,
+}
+
+impl Track {
+    const ALL: [Track; 3] = [Track::Menu, Track::Game, Track::GameOver];
+
+    fn for_state(state: &AppState) -> Option<Self> {
+        match state {
+            AppState::Loading => None,
+            AppState::Start | AppState::Settings => Some(Track::Menu),
+            AppState::InGame | AppState::Paused => Some(Track::Game),
+            AppState::GameOver => Some(Track::GameOver),
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            Track::Menu => "music/menu.wav",
+            Track::Game => "music/game.wav",
+            Track::GameOver => "music/game_over.wav",
+        }
+    }
+}
+
+/// Handles of every effect and track, part of the
+/// [`GameAssets`].
+pub struct Sounds {
+    effects: HashMap<Sfx, Handle<AudioSource>>,
+    music: HashMap<Track, Handle<AudioSource>>,
+    engine: Handle<AudioSource>,
+}
+
+impl Sounds {
+    pub fn load(asset_server: &AssetServer) -> Self {
+        Sounds {
+            effects: Sfx::ALL
+                .into_iter()
+                .map(|sfx| (sfx, asset_server.load(sfx.path())))
+                .collect(),
+            music: Track::ALL
+                .into_iter()
+                .map(|track| (track, asset_server.load(track.path())))
+                .collect(),
+            engine: asset_server.load("sounds/thrust.wav"),
+        }
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = HandleId> + '_ {
+        self.effects
+            .values()
+            .chain(self.music.values())
+            .chain([&self.engine])
+            .map(|h| h.id)
+    }
+}
+
+/// Playing music track and the looping engine rumble.
+#[derive(Default)]
+pub struct Mixer {
+    track: Option<Track>,
+    music: Option<Handle<AudioSink>>,
+    engine: Option<Handle<AudioSink>>,
+}
+
+/// Plays the effects asked for this frame, each one at most once so that a
+/// wave of hits does not stack into a single loud blast.
+pub fn play_sfx(
+    mut events: EventReader<PlaySfx>,
+    audio: Res<Audio>,
+    assets: Res<GameAssets>,
+    settings: Res<Settings>,
+) {
+    let volume = settings.volume(Channel::Effects);
+    let requested: HashSet<Sfx> = events.iter().map(|PlaySfx(sfx)| *sfx).collect();
+    if volume <= 0. {
+        return;
+    }
+    for sfx in requested {
+        audio.play_with_settings(
+            assets.sounds.effects[&sfx].clone(),
+            PlaybackSettings::ONCE.with_volume(volume),
+        );
+    }
+}
+
+/// Clicks for every button pressed in the menus.
+pub fn click_sounds(
+    buttons: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    focused: Query<(), With<Focused>>,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<Input<GamepadButton>>,
+    mut sfx: EventWriter<PlaySfx>,
+) {
+    let pressed_focus = !focused.is_empty()
+        && any_gamepad_just_pressed(&gamepads, &pad_buttons, &[GamepadButtonType::South]);
+    if pressed_focus || buttons.iter().any(|i| *i == Interaction::Clicked) {
+        sfx.send(PlaySfx(Sfx::Click));
+    }
+}
+
+/// Switches tracks with the app state and keeps the music volume in line
+/// with the settings. The music is turned down while paused.
+pub fn play_music(
+    state: Res<State<AppState>>,
+    settings: Res<Settings>,
+    audio: Res<Audio>,
+    assets: Res<GameAssets>,
+    sinks: Res<Assets<AudioSink>>,
+    mut mixer: ResMut<Mixer>,
+) {
+    let mut volume = settings.volume(Channel::Music);
+    if *state.current() == AppState::Paused {
+        volume *= 0.4;
+    }
+
+    let track = Track::for_state(state.current());
+    if mixer.track != track {
+        if let Some(sink) = mixer.music.take().and_then(|h| sinks.get(&h)) {
+            sink.stop();
+        }
+        if let Some(track) = track {
+            let sink = audio.play_with_settings(
+                assets.sounds.music[&track].clone(),
+                PlaybackSettings::LOOP.with_volume(volume),
+            );
+            mixer.music = Some(sinks.get_handle(sink));
+        }
+        mixer.track = track;
+    } else if state.is_changed() || settings.is_changed() {
+        if let Some(sink) = mixer.music.as_ref().and_then(|h| sinks.get(h)) {
+            sink.set_volume(volume);
+        }
+    }
+}
+
+/// Follows the thrust input with the engine loop while in game. The loop is
+/// started silent once and left running, only its volume changes.
+pub fn engine_sound(
+    state: Res<State<AppState>>,
+    settings: Res<Settings>,
+    actions: Res<Actions>,
+    audio: Res<Audio>,
+    assets: Res<GameAssets>,
+    sinks: Res<Assets<AudioSink>>,
+    mut mixer: ResMut<Mixer>,
+) {
+    if mixer.engine.is_none() {
+        let sink = audio.play_with_settings(
+            assets.sounds.engine.clone(),
+            PlaybackSettings::LOOP.with_volume(0.),
+        );
+        mixer.engine = Some(sinks.get_handle(sink));
+    }
+
+    let thrust = if *state.current() == AppState::InGame {
+        actions.value(Action::Thrust)
+    } else {
+        0.
+    };
+    if let Some(sink) = mixer.engine.as_ref().and_then(|h| sinks.get(h)) {
+        sink.set_volume(0.5 * thrust * settings.volume(Channel::Effects));
+    }
+}
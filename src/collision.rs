@@ -0,0 +1,614 @@
+//! Separating axis collision tests between oriented boxes, circles and convex polygons.
+
+use bevy::prelude::*;
+
+/// Convex outline centered on the entity it belongs to.
+#[derive(Clone, Copy, Debug)]
+pub enum Shape {
+    /// Box of the given full width and height.
+    Rect(Vec2),
+    Circle(f32),
+    /// Convex polygon, vertices relative to the center.
+    Polygon(&'static [Vec2]),
+}
+
+impl Shape {
+    /// Places the shape in the world, `rotation` in radians around the z axis.
+    pub fn at(&self, position: Vec2, rotation: f32) -> Placed<'_> {
+        Placed {
+            shape: self,
+            position,
+            rotation,
+        }
+    }
+
+    /// Radius of the smallest circle around the center containing the shape.
+    pub fn radius(&self) -> f32 {
+        match self {
+            Shape::Rect(size) => size.length() / 2.,
+            Shape::Circle(r) => *r,
+            Shape::Polygon(points) => points.iter().map(|p| p.length()).fold(0., f32::max),
+        }
+    }
+}
+
+/// A [`Shape`] with a position and a rotation.
+#[derive(Clone, Copy)]
+pub struct Placed<'a> {
+    pub shape: &'a Shape,
+    pub position: Vec2,
+    pub rotation: f32,
+}
+
+impl<'a> Placed<'a> {
+    fn vertices(&self) -> Vec<Vec2> {
+        let rot = Vec2::from_angle(self.rotation);
+        match self.shape {
+            Shape::Rect(size) => [(0.5, 0.5), (-0.5, 0.5), (-0.5, -0.5), (0.5, -0.5)]
+                .into_iter()
+                .map(|(x, y)| self.position + rot.rotate(Vec2::new(size.x * x, size.y * y)))
+                .collect(),
+            Shape::Polygon(points) => points
+                .iter()
+                .map(|p| self.position + rot.rotate(*p))
+                .collect(),
+            Shape::Circle(_) => vec![self.position],
+        }
+    }
+
+    /// Candidate separating axes contributed by this shape's edges.
+    fn axes(&self, vertices: &[Vec2]) -> Vec<Vec2> {
+        match self.shape {
+            Shape::Rect(_) => {
+                let x = Vec2::from_angle(self.rotation);
+                vec![x, x.perp()]
+            }
+            Shape::Polygon(_) => (0..vertices.len())
+                .map(|i| (vertices[(i + 1) % vertices.len()] - vertices[i]).perp())
+                .map(|n| n.normalize_or_zero())
+                .filter(|n| *n != Vec2::ZERO)
+                .collect(),
+            Shape::Circle(_) => vec![],
+        }
+    }
+}
+
+/// How two overlapping shapes touch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    /// Unit vector pointing from the first shape toward the second.
+    pub normal: Vec2,
+    /// Distance the second shape has to move along `normal` to stop overlapping.
+    pub depth: f32,
+}
+
+/// Shortest displacement from `from` to `to` on a field of `size` centered on
+/// the origin whose opposite edges are joined.
+pub fn wrapped_delta(from: Vec2, to: Vec2, size: Vec2) -> Vec2 {
+    let d = to - from;
+    d - size * (d / size).round()
+}
+
+/// Tests two placed shapes for overlap. Touching edges do not count.
+pub fn collide(a: &Placed, b: &Placed) -> Option<Contact> {
+    match (a.shape, b.shape) {
+        (Shape::Circle(ra), Shape::Circle(rb)) => {
+            let d = b.position - a.position;
+            let distance = d.length();
+            let depth = ra + rb - distance;
+            if depth <= 0. {
+                return None;
+            }
+            let normal = if distance > f32::EPSILON {
+                d / distance
+            } else {
+                Vec2::Y
+            };
+            Some(Contact { normal, depth })
+        }
+        (Shape::Circle(r), _) => polygon_circle(b, a.position, *r).map(|c| Contact {
+            normal: -c.normal,
+            depth: c.depth,
+        }),
+        (_, Shape::Circle(r)) => polygon_circle(a, b.position, *r),
+        _ => {
+            let va = a.vertices();
+            let vb = b.vertices();
+            let mut axes = a.axes(&va);
+            axes.extend(b.axes(&vb));
+            sat(&axes, |axis| project(&va, axis), |axis| project(&vb, axis))
+        }
+    }
+}
+
+/// Like [`collide`], but `a` is swept along `motion`, from `a.position - motion`
+/// to `a.position`, so a thin fast shape cannot step over `b` between two frames.
+/// A swept circle is approximated by its bounding square.
+pub fn collide_swept(a: &Placed, motion: Vec2, b: &Placed) -> Option<Contact> {
+    if motion == Vec2::ZERO {
+        return collide(a, b);
+    }
+    let square;
+    let a = match a.shape {
+        Shape::Circle(r) => {
+            square = Shape::Rect(Vec2::splat(2. * r));
+            square.at(a.position, 0.)
+        }
+        _ => *a,
+    };
+
+    // The swept area is the convex hull of the shape at both ends of the
+    // motion. Projecting the corners of both copies gives the same interval as
+    // projecting the hull, so the hull itself is never built.
+    let end = a.vertices();
+    let mut points = end.clone();
+    points.extend(end.iter().map(|p| *p - motion));
+    let mut axes = a.axes(&end);
+    axes.push(motion.perp().normalize());
+
+    match b.shape {
+        Shape::Circle(r) => {
+            let center = b.position;
+            if let Some(axis) = closest_axis(&points, center) {
+                axes.push(axis);
+            }
+            sat(
+                &axes,
+                |axis| project(&points, axis),
+                |axis| {
+                    let c = center.dot(axis);
+                    (c - r, c + r)
+                },
+            )
+        }
+        _ => {
+            let vb = b.vertices();
+            axes.extend(b.axes(&vb));
+            sat(
+                &axes,
+                |axis| project(&points, axis),
+                |axis| project(&vb, axis),
+            )
+        }
+    }
+}
+
+/// Axis from the point closest to `center` toward `center`.
+fn closest_axis(points: &[Vec2], center: Vec2) -> Option<Vec2> {
+    let closest = points.iter().min_by(|p, q| {
+        p.distance_squared(center)
+            .total_cmp(&q.distance_squared(center))
+    })?;
+    let axis = (center - *closest).normalize_or_zero();
+    (axis != Vec2::ZERO).then_some(axis)
+}
+
+fn polygon_circle(polygon: &Placed, center: Vec2, radius: f32) -> Option<Contact> {
+    let vertices = polygon.vertices();
+    let mut axes = polygon.axes(&vertices);
+    // The axis through the closest vertex catches circles next to a corner
+    if let Some(axis) = closest_axis(&vertices, center) {
+        axes.push(axis);
+    }
+    sat(
+        &axes,
+        |axis| project(&vertices, axis),
+        |axis| {
+            let c = center.dot(axis);
+            (c - radius, c + radius)
+        },
+    )
+}
+
+/// Projects a set of points on `axis`, returning the covered interval.
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+    points
+        .iter()
+        .map(|p| p.dot(axis))
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+            (min.min(d), max.max(d))
+        })
+}
+
+/// Runs the separating axis test over `axes`, keeping the axis of least
+/// penetration. `normal` ends up pointing from `a` toward `b`.
+fn sat(
+    axes: &[Vec2],
+    project_a: impl Fn(Vec2) -> (f32, f32),
+    project_b: impl Fn(Vec2) -> (f32, f32),
+) -> Option<Contact> {
+    let mut best: Option<Contact> = None;
+    for &axis in axes {
+        let (min_a, max_a) = project_a(axis);
+        let (min_b, max_b) = project_b(axis);
+        // Push needed to separate `b` along +axis or along -axis
+        let forward = max_a - min_b;
+        let backward = max_b - min_a;
+        if forward <= 0. || backward <= 0. {
+            return None;
+        }
+        let contact = if forward < backward {
+            Contact {
+                normal: axis,
+                depth: forward,
+            }
+        } else {
+            Contact {
+                normal: -axis,
+                depth: backward,
+            }
+        };
+        if best.is_none_or(|b| contact.depth < b.depth) {
+            best = Some(contact);
+        }
+    }
+    best
+}
+
+/// Uniform grid over the wrapping playfield, used as a broad phase so that
+/// only nearby pairs reach the narrow phase tests above.
+///
+/// Items are filed under the cell holding their center, queries look far
+/// enough around to catch anything within the largest inserted radius. Cell
+/// coordinates wrap like the playfield does, so items close to the opposite
+/// edge are found as well.
+pub struct SpatialGrid<T> {
+    size: Vec2,
+    cols: i32,
+    rows: i32,
+    cell: Vec2,
+    max_radius: f32,
+    cells: Vec<Vec<T>>,
+}
+
+impl<T: Copy> SpatialGrid<T> {
+    /// Grid over a field of `size` centered on the origin, split into cells
+    /// of roughly `cell` on each side.
+    pub fn new(size: Vec2, cell: f32) -> Self {
+        let cols = ((size.x / cell).round() as i32).max(1);
+        let rows = ((size.y / cell).round() as i32).max(1);
+        SpatialGrid {
+            size,
+            cols,
+            rows,
+            cell: size / Vec2::new(cols as f32, rows as f32),
+            max_radius: 0.,
+            cells: vec![Vec::new(); (cols * rows) as usize],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(Vec::clear);
+        self.max_radius = 0.;
+    }
+
+    /// Adds an item whose bounds fit in the circle at `position`.
+    pub fn insert(&mut self, item: T, position: Vec2, radius: f32) {
+        let (x, y) = self.coords(position);
+        let i = self.index(x, y);
+        self.cells[i].push(item);
+        self.max_radius = self.max_radius.max(radius);
+    }
+
+    /// Items that may overlap the circle at `position`. Each one is yielded
+    /// at most once.
+    pub fn query(&self, position: Vec2, radius: f32) -> impl Iterator<Item = T> + '_ {
+        let reach = Vec2::splat(radius + self.max_radius);
+        let (x0, y0) = self.coords(position - reach);
+        let (x1, y1) = self.coords(position + reach);
+        // Past a full turn the wrapped range would visit cells twice
+        let x1 = x1.min(x0 + self.cols - 1);
+        let y1 = y1.min(y0 + self.rows - 1);
+        (y0..=y1)
+            .flat_map(move |y| (x0..=x1).map(move |x| self.index(x, y)))
+            .flat_map(move |i| self.cells[i].iter().copied())
+    }
+
+    fn coords(&self, position: Vec2) -> (i32, i32) {
+        let c = ((position + self.size / 2.) / self.cell).floor();
+        (c.x as i32, c.y as i32)
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y.rem_euclid(self.rows) * self.cols + x.rem_euclid(self.cols)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    static SQUARE: Shape = Shape::Rect(Vec2::new(20., 20.));
+    static TRIANGLE: Shape = Shape::Polygon(&[
+        Vec2::new(0., 10.),
+        Vec2::new(-10., -10.),
+        Vec2::new(10., -10.),
+    ]);
+
+    fn assert_contact(contact: Option<Contact>, normal: Vec2, depth: f32) {
+        let contact = contact.expect("shapes should overlap");
+        assert!(
+            contact.normal.abs_diff_eq(normal, 1e-4),
+            "normal {:?}, expected {:?}",
+            contact.normal,
+            normal
+        );
+        assert!(
+            (contact.depth - depth).abs() < 1e-4,
+            "depth {}, expected {}",
+            contact.depth,
+            depth
+        );
+    }
+
+    #[test]
+    fn thin_box_across_the_middle() {
+        // A cross: no corner of either box is inside the other
+        let wide = Shape::Rect(Vec2::new(200., 10.));
+        let tall = Shape::Rect(Vec2::new(10., 200.));
+        assert!(collide(&wide.at(Vec2::ZERO, 0.), &tall.at(Vec2::ZERO, 0.)).is_some());
+        assert!(collide(&tall.at(Vec2::ZERO, 0.), &wide.at(Vec2::ZERO, 0.)).is_some());
+    }
+
+    #[test]
+    fn rotated_thin_box_across_the_middle() {
+        let wide = Shape::Rect(Vec2::new(200., 10.));
+        let a = wide.at(Vec2::ZERO, std::f32::consts::FRAC_PI_4);
+        let b = wide.at(Vec2::ZERO, -std::f32::consts::FRAC_PI_4);
+        assert!(collide(&a, &b).is_some());
+    }
+
+    #[test]
+    fn touching_edges_do_not_collide() {
+        let a = SQUARE.at(Vec2::ZERO, 0.);
+        assert!(collide(&a, &SQUARE.at(Vec2::new(20., 0.), 0.)).is_none());
+        assert!(collide(&a, &SQUARE.at(Vec2::new(0., -20.), 0.)).is_none());
+        let circle = Shape::Circle(5.);
+        assert!(collide(&a, &circle.at(Vec2::new(15., 0.), 0.)).is_none());
+        assert!(collide(
+            &circle.at(Vec2::ZERO, 0.),
+            &circle.at(Vec2::new(10., 0.), 0.)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn separate_shapes_do_not_collide() {
+        let a = SQUARE.at(Vec2::ZERO, 0.);
+        assert!(collide(&a, &SQUARE.at(Vec2::new(30., 5.), 0.)).is_none());
+        assert!(collide(&a, &TRIANGLE.at(Vec2::new(0., 25.), 0.)).is_none());
+        // Bounding boxes overlap, the rotated square still misses the corner
+        let diamond = SQUARE.at(Vec2::new(22., 22.), std::f32::consts::FRAC_PI_4);
+        assert!(collide(&a, &diamond).is_none());
+    }
+
+    #[test]
+    fn penetration_normal_points_from_first_to_second() {
+        let a = SQUARE.at(Vec2::ZERO, 0.);
+        assert_contact(collide(&a, &SQUARE.at(Vec2::new(15., 2.), 0.)), Vec2::X, 5.);
+        assert_contact(
+            collide(&SQUARE.at(Vec2::new(15., 2.), 0.), &a),
+            -Vec2::X,
+            5.,
+        );
+        assert_contact(
+            collide(&a, &SQUARE.at(Vec2::new(-3., -18.), 0.)),
+            -Vec2::Y,
+            2.,
+        );
+    }
+
+    #[test]
+    fn polygon_against_box() {
+        let a = SQUARE.at(Vec2::ZERO, 0.);
+        assert_contact(
+            collide(&a, &TRIANGLE.at(Vec2::new(0., 18.), 0.)),
+            Vec2::Y,
+            2.,
+        );
+    }
+
+    #[test]
+    fn circle_against_polygon() {
+        let a = SQUARE.at(Vec2::ZERO, 0.);
+        let circle = Shape::Circle(5.);
+        assert_contact(collide(&a, &circle.at(Vec2::new(12., 0.), 0.)), Vec2::X, 3.);
+        assert_contact(
+            collide(&circle.at(Vec2::new(12., 0.), 0.), &a),
+            -Vec2::X,
+            3.,
+        );
+        // Inside the bounding box of the square but past its corner
+        assert!(collide(&a, &circle.at(Vec2::new(14., 14.), 0.)).is_none());
+        assert_contact(
+            collide(&a, &circle.at(Vec2::new(13., 13.), 0.)),
+            Vec2::ONE.normalize(),
+            5. - 3. * 2f32.sqrt(),
+        );
+    }
+
+    #[test]
+    fn circle_against_circle() {
+        let a = Shape::Circle(5.);
+        let b = Shape::Circle(3.);
+        assert_contact(
+            collide(&a.at(Vec2::ZERO, 0.), &b.at(Vec2::new(0., -6.), 0.)),
+            -Vec2::Y,
+            2.,
+        );
+        assert!(collide(&a.at(Vec2::ZERO, 0.), &b.at(Vec2::new(6., 6.), 0.)).is_none());
+    }
+
+    static BULLET: Shape = Shape::Rect(Vec2::new(5., 17.));
+
+    #[test]
+    fn fast_bullet_passing_through_in_one_step() {
+        let enemy = SQUARE.at(Vec2::ZERO, 0.);
+        // From 100 below the enemy to 100 above it
+        let bullet = BULLET.at(Vec2::new(0., 100.), 0.);
+        let motion = Vec2::new(0., 200.);
+        assert!(collide(&bullet, &enemy).is_none());
+        assert!(collide(&BULLET.at(bullet.position - motion, 0.), &enemy).is_none());
+        assert!(collide_swept(&bullet, motion, &enemy).is_some());
+        let round = Shape::Circle(10.);
+        assert!(collide_swept(&bullet, motion, &round.at(Vec2::ZERO, 0.)).is_some());
+    }
+
+    #[test]
+    fn fast_bullet_near_miss() {
+        let enemy = SQUARE.at(Vec2::ZERO, 0.);
+        let beside = BULLET.at(Vec2::new(15., 100.), 0.);
+        assert!(collide_swept(&beside, Vec2::new(0., 200.), &enemy).is_none());
+        // Diagonal path passing above the top left corner. The bounding boxes
+        // of the path and of the enemy overlap.
+        let diagonal = BULLET.at(Vec2::new(100., 160.), 0.);
+        let motion = Vec2::new(200., 200.);
+        assert!(collide_swept(&diagonal, motion, &enemy).is_none());
+        let round = Shape::Circle(10.);
+        assert!(collide_swept(&diagonal, motion, &round.at(Vec2::ZERO, 0.)).is_none());
+    }
+
+    #[test]
+    fn sweep_without_motion_is_a_plain_test() {
+        let enemy = SQUARE.at(Vec2::ZERO, 0.);
+        let bullet = BULLET.at(Vec2::new(0., 15.), 0.);
+        assert_eq!(
+            collide_swept(&bullet, Vec2::ZERO, &enemy),
+            collide(&bullet, &enemy)
+        );
+    }
+
+    const FIELD: Vec2 = Vec2::new(1080., 720.);
+
+    fn grid_with(items: &[(usize, Vec2)]) -> SpatialGrid<usize> {
+        let mut grid = SpatialGrid::new(FIELD, 60.);
+        for (item, position) in items {
+            grid.insert(*item, *position, 10.);
+        }
+        grid
+    }
+
+    fn found(grid: &SpatialGrid<usize>, position: Vec2) -> Vec<usize> {
+        let mut items: Vec<_> = grid.query(position, 5.).collect();
+        items.sort_unstable();
+        items
+    }
+
+    #[test]
+    fn wrapped_delta_takes_the_short_way() {
+        let delta = wrapped_delta(Vec2::new(535., 355.), Vec2::new(-535., -355.), FIELD);
+        assert!(delta.abs_diff_eq(Vec2::new(10., 10.), 1e-4));
+        let delta = wrapped_delta(Vec2::new(-100., 0.), Vec2::new(100., 0.), FIELD);
+        assert!(delta.abs_diff_eq(Vec2::new(200., 0.), 1e-4));
+    }
+
+    #[test]
+    fn grid_finds_neighbours_across_left_and_right_edges() {
+        let grid = grid_with(&[(1, Vec2::new(535., 0.)), (2, Vec2::new(0., 0.))]);
+        assert_eq!(found(&grid, Vec2::new(-535., 0.)), vec![1]);
+        let grid = grid_with(&[(1, Vec2::new(-535., 100.))]);
+        assert_eq!(found(&grid, Vec2::new(538., 100.)), vec![1]);
+    }
+
+    #[test]
+    fn grid_finds_neighbours_across_top_and_bottom_edges() {
+        let grid = grid_with(&[(1, Vec2::new(0., -355.)), (2, Vec2::new(0., 0.))]);
+        assert_eq!(found(&grid, Vec2::new(0., 358.)), vec![1]);
+        let grid = grid_with(&[(1, Vec2::new(-200., 355.))]);
+        assert_eq!(found(&grid, Vec2::new(-200., -359.)), vec![1]);
+    }
+
+    #[test]
+    fn grid_finds_neighbours_across_a_corner() {
+        let grid = grid_with(&[(1, Vec2::new(535., 355.)), (2, Vec2::new(535., 0.))]);
+        assert_eq!(found(&grid, Vec2::new(-535., -355.)), vec![1]);
+    }
+
+    #[test]
+    fn grid_yields_items_once_for_huge_queries() {
+        let grid = grid_with(&[(1, Vec2::ZERO), (2, Vec2::new(500., -300.))]);
+        let mut items: Vec<_> = grid.query(Vec2::ZERO, 5000.).collect();
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2]);
+    }
+
+    static ENEMY: Shape = Shape::Rect(Vec2::new(40., 57.));
+
+    fn naive_pairs(bullets: &[Vec2], enemies: &[Vec2]) -> usize {
+        let mut pairs = 0;
+        for bp in bullets {
+            for ep in enemies {
+                if bp.distance(*ep) < BULLET.radius() + ENEMY.radius() {
+                    pairs += 1;
+                }
+            }
+        }
+        pairs
+    }
+
+    fn grid_pairs(grid: &mut SpatialGrid<usize>, bullets: &[Vec2], enemies: &[Vec2]) -> usize {
+        grid.clear();
+        for (i, ep) in enemies.iter().enumerate() {
+            grid.insert(i, *ep, ENEMY.radius());
+        }
+        let mut pairs = 0;
+        for bp in bullets {
+            for i in grid.query(*bp, BULLET.radius()) {
+                if bp.distance(enemies[i]) < BULLET.radius() + ENEMY.radius() {
+                    pairs += 1;
+                }
+            }
+        }
+        pairs
+    }
+
+    fn time(mut f: impl FnMut() -> usize) -> (usize, Duration) {
+        const ROUNDS: u32 = 20;
+        let start = Instant::now();
+        let mut pairs = 0;
+        for _ in 0..ROUNDS {
+            pairs = f();
+        }
+        (pairs, start.elapsed() / ROUNDS)
+    }
+
+    /// Compares the grid against testing every bullet with every enemy. Both
+    /// stop at the bounding circle test deciding which pairs go on to the
+    /// narrow phase, which costs the same either way.
+    ///
+    /// ```bash
+    /// cargo test --release broad_phase_speed -- --ignored --nocapture
+    /// ```
+    #[test]
+    #[ignore]
+    fn broad_phase_speed() {
+        // Small deterministic generator, so both runs see the same field
+        let mut seed = 0x5eed_u64;
+        let mut point = || {
+            let mut next = || {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (seed >> 40) as f32 / (1u64 << 24) as f32
+            };
+            (Vec2::new(next(), next()) - 0.5) * FIELD
+        };
+
+        let mut cells = SpatialGrid::new(FIELD, 60.);
+        for count in [100, 1_000, 5_000] {
+            let bullets: Vec<_> = (0..count).map(|_| point()).collect();
+            let enemies: Vec<_> = (0..count).map(|_| point()).collect();
+
+            let (naive, naive_time) = time(|| naive_pairs(&bullets, &enemies));
+            let (grid, grid_time) = time(|| grid_pairs(&mut cells, &bullets, &enemies));
+            assert_eq!(naive, grid);
+
+            println!(
+                "{count:>5} bullets x {count:>5} enemies: all pairs {naive_time:>10.2?}, grid {grid_time:>10.2?} ({:.1}x faster)",
+                naive_time.as_secs_f64() / grid_time.as_secs_f64(),
+            );
+        }
+    }
+}
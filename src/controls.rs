@@ -0,0 +1,147 @@
+//! Player actions and the keyboard and gamepad inputs driving them. Touch
+//! input is added on top by the `touch` module.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Thrust,
+    TurnLeft,
+    TurnRight,
+    Fire,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] = [
+        Action::Thrust,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Fire,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Thrust => "Thrust",
+            Action::TurnLeft => "Turn left",
+            Action::TurnRight => "Turn right",
+            Action::Fire => "Fire",
+        }
+    }
+}
+
+/// Keys bound to each action.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bindings(HashMap<Action, Vec<KeyCode>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings(HashMap::from([
+            (Action::Thrust, vec![KeyCode::W, KeyCode::Up]),
+            (Action::TurnLeft, vec![KeyCode::A, KeyCode::Left]),
+            (Action::TurnRight, vec![KeyCode::D, KeyCode::Right]),
+            (Action::Fire, vec![KeyCode::Space]),
+        ]))
+    }
+}
+
+impl Bindings {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Makes `key` the only key of `action`, taking it away from other actions.
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        for keys in self.0.values_mut() {
+            keys.retain(|k| *k != key);
+        }
+        self.0.insert(action, vec![key]);
+    }
+
+    /// Bound keys as shown in the settings screen.
+    pub fn describe(&self, action: Action) -> String {
+        let keys: Vec<_> = self.keys(action).iter().map(|k| format!("{k:?}")).collect();
+        if keys.is_empty() {
+            "-".to_string()
+        } else {
+            keys.join(", ")
+        }
+    }
+}
+
+/// How far each action is held this frame, from 0 to 1. Keys are all or
+/// nothing, sticks and triggers give anything in between.
+#[derive(Default)]
+pub struct Actions(HashMap<Action, f32>);
+
+impl Actions {
+    pub fn value(&self, action: Action) -> f32 {
+        self.0.get(&action).copied().unwrap_or(0.)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action) > 0.5
+    }
+
+    /// Holds `action` at least as far as `value`, clamped to 0..=1.
+    pub fn hold(&mut self, action: Action, value: f32) {
+        let held = self.0.entry(action).or_default();
+        *held = held.max(value.clamp(0., 1.));
+    }
+}
+
+pub fn update_actions(
+    mut actions: ResMut<Actions>,
+    settings: Res<Settings>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<Input<GamepadButton>>,
+    pad_triggers: Res<Axis<GamepadButton>>,
+    pad_axes: Res<Axis<GamepadAxis>>,
+) {
+    actions.0.clear();
+    for action in Action::ALL {
+        if settings
+            .bindings
+            .keys(action)
+            .iter()
+            .any(|k| keyboard.pressed(*k))
+        {
+            actions.hold(action, 1.);
+        }
+    }
+
+    for gamepad in gamepads.iter() {
+        let axis = |t| pad_axes.get(GamepadAxis::new(*gamepad, t)).unwrap_or(0.);
+        let trigger = |t| {
+            pad_triggers
+                .get(GamepadButton::new(*gamepad, t))
+                .unwrap_or(0.)
+        };
+        let stick = Vec2::new(
+            axis(GamepadAxisType::LeftStickX),
+            axis(GamepadAxisType::LeftStickY),
+        );
+        let fire = if pad_buttons.pressed(GamepadButton::new(*gamepad, GamepadButtonType::South)) {
+            1.
+        } else {
+            trigger(GamepadButtonType::RightTrigger2)
+        };
+
+        for (action, value) in [
+            (
+                Action::Thrust,
+                stick.y.max(trigger(GamepadButtonType::LeftTrigger2)),
+            ),
+            (Action::TurnLeft, -stick.x),
+            (Action::TurnRight, stick.x),
+            (Action::Fire, fire),
+        ] {
+            actions.hold(action, value);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Gamepad navigation through menu buttons.
+//!
+//! The D-pad or the left stick moves a highlight to the nearest button in
+//! that direction, and menus treat South on the [`Focused`] button like a
+//! click. Nothing is focused until the first move, so menus still work with
+//! the mouse alone.
+
+use bevy::{prelude::*, utils::FloatOrd};
+
+const HIGHLIGHT: Color = Color::WHITE;
+/// Stick deflection that counts as a push. The stick has to come back under
+/// it before it moves the focus again.
+const STICK_PUSH: f32 = 0.5;
+
+/// Button the gamepad is on, with the color it had before being highlighted.
+#[derive(Component)]
+pub struct Focused(UiColor);
+
+pub fn move_focus(
+    mut commands: Commands,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<Input<GamepadButton>>,
+    pad_axes: Res<Axis<GamepadAxis>>,
+    mut stick_held: Local<bool>,
+    mut buttons: Query<(Entity, &GlobalTransform, &mut UiColor, Option<&Focused>), With<Button>>,
+) {
+    let mut step = Vec2::ZERO;
+    let mut stick = Vec2::ZERO;
+    for gamepad in gamepads.iter() {
+        for (button, direction) in [
+            (GamepadButtonType::DPadUp, Vec2::Y),
+            (GamepadButtonType::DPadDown, -Vec2::Y),
+            (GamepadButtonType::DPadLeft, -Vec2::X),
+            (GamepadButtonType::DPadRight, Vec2::X),
+        ] {
+            if pad_buttons.just_pressed(GamepadButton::new(*gamepad, button)) {
+                step = direction;
+            }
+        }
+        let axis = |t| pad_axes.get(GamepadAxis::new(*gamepad, t)).unwrap_or(0.);
+        let pad_stick = Vec2::new(
+            axis(GamepadAxisType::LeftStickX),
+            axis(GamepadAxisType::LeftStickY),
+        );
+        if pad_stick.length() > stick.length() {
+            stick = pad_stick;
+        }
+    }
+    let pushed = stick.length() > STICK_PUSH;
+    if pushed && !*stick_held && step == Vec2::ZERO {
+        step = if stick.x.abs() > stick.y.abs() {
+            Vec2::new(stick.x.signum(), 0.)
+        } else {
+            Vec2::new(0., stick.y.signum())
+        };
+    }
+    *stick_held = pushed;
+    if step == Vec2::ZERO {
+        return;
+    }
+
+    // UI positions grow upward, like the stick
+    let focused = buttons
+        .iter()
+        .find(|(.., focused)| focused.is_some())
+        .map(|(e, t, ..)| (e, t.translation().truncate()));
+    let next = match focused {
+        // Nearest button ahead, counting sideways distance double so that
+        // moves stay in their row or column when they can
+        Some((current, from)) => buttons
+            .iter()
+            .filter_map(|(e, t, ..)| {
+                let delta = t.translation().truncate() - from;
+                let ahead = delta.dot(step);
+                (ahead > 1.).then(|| (e, ahead + 2. * delta.perp_dot(step).abs()))
+            })
+            .min_by_key(|(_, score)| FloatOrd(*score))
+            .map_or(current, |(e, _)| e),
+        // The first move lands on the top left button
+        None => match buttons.iter().min_by_key(|(_, t, ..)| {
+            let position = t.translation();
+            (FloatOrd(-position.y), FloatOrd(position.x))
+        }) {
+            Some((e, ..)) => e,
+            None => return,
+        },
+    };
+    if focused.map(|(e, _)| e) == Some(next) {
+        return;
+    }
+
+    if let Some((current, _)) = focused {
+        if let Ok((_, _, mut color, Some(Focused(original)))) = buttons.get_mut(current) {
+            *color = *original;
+        }
+        commands.entity(current).remove::<Focused>();
+    }
+    if let Ok((_, _, mut color, _)) = buttons.get_mut(next) {
+        commands.entity(next).insert(Focused(*color));
+        *color = HIGHLIGHT.into();
+    }
+}
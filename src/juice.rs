@@ -0,0 +1,135 @@
+//! Game feel: trauma based camera shake, hit-stop and screen flashes.
+//!
+//! Gameplay sends [`Juice`] events when something worth feeling happens, and
+//! the effects turned off in the settings are left out here. Shake and
+//! flashes run in real time, so they keep going through slow motion and
+//! the hit-stop itself.
+
+use bevy::prelude::*;
+
+use crate::{get_rand01, settings::Settings, GameTime};
+
+/// Largest camera offset, in playfield units.
+const MAX_OFFSET: f32 = 18.;
+/// Largest camera roll, in radians.
+const MAX_ROLL: f32 = 0.05;
+/// Trauma lost per second.
+const TRAUMA_DECAY: f32 = 1.3;
+const FLASH_SECONDS: f32 = 0.25;
+
+pub struct Juice {
+    /// Added to the camera trauma.
+    pub trauma: f32,
+    /// Real seconds the game freezes for.
+    pub hit_stop: f32,
+    pub flash: Option<Color>,
+}
+
+impl Juice {
+    pub const KILL: Juice = Juice {
+        trauma: 0.15,
+        hit_stop: 0.,
+        flash: None,
+    };
+    /// Enemies that split and boss turrets.
+    pub const BIG_KILL: Juice = Juice {
+        trauma: 0.35,
+        hit_stop: 0.05,
+        flash: None,
+    };
+    pub const BOSS_KILL: Juice = Juice {
+        trauma: 0.8,
+        hit_stop: 0.15,
+        flash: Some(Color::rgba(1., 1., 1., 0.6)),
+    };
+    pub const PLAYER_DEATH: Juice = Juice {
+        trauma: 1.,
+        hit_stop: 0.1,
+        flash: Some(Color::rgba(1., 0.25, 0.1, 0.5)),
+    };
+}
+
+/// Camera shake from 0 to 1. The shake grows with its square, so small
+/// knocks barely move the camera while big ones stack up quickly.
+#[derive(Default)]
+pub struct Trauma(f32);
+
+/// Full screen overlay fading out.
+#[derive(Component)]
+pub struct Flash {
+    color: Color,
+    timer: Timer,
+}
+
+pub fn apply_juice(
+    mut commands: Commands,
+    mut events: EventReader<Juice>,
+    settings: Res<Settings>,
+    mut trauma: ResMut<Trauma>,
+    mut game_time: ResMut<GameTime>,
+) {
+    for juice in events.iter() {
+        if settings.screen_shake {
+            trauma.0 = (trauma.0 + juice.trauma).min(1.);
+        }
+        if settings.hit_stop {
+            game_time.freeze(juice.hit_stop);
+        }
+        if let Some(color) = juice.flash.filter(|_| settings.flashes) {
+            commands
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                        ..default()
+                    },
+                    color: color.into(),
+                    ..default()
+                })
+                .insert(Flash {
+                    color,
+                    timer: Timer::from_seconds(FLASH_SECONDS, false),
+                });
+        }
+    }
+}
+
+pub fn shake_camera(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut trauma: ResMut<Trauma>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    if !settings.screen_shake {
+        trauma.0 = 0.;
+    }
+    let shake = trauma.0 * trauma.0;
+    let noise = || get_rand01() as f32 * 2. - 1.;
+    for mut camera in &mut cameras {
+        camera.translation.x = MAX_OFFSET * shake * noise();
+        camera.translation.y = MAX_OFFSET * shake * noise();
+        camera.rotation = Quat::from_rotation_z(MAX_ROLL * shake * noise());
+    }
+    trauma.0 = (trauma.0 - TRAUMA_DECAY * time.delta_seconds()).max(0.);
+}
+
+/// Stops any shake left when the game field goes away.
+pub fn calm_camera(mut trauma: ResMut<Trauma>) {
+    trauma.0 = 0.;
+}
+
+pub fn fade_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut Flash, &mut UiColor)>,
+) {
+    for (entity, mut flash, mut color) in &mut flashes {
+        if flash.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let alpha = flash.color.a() * flash.timer.percent_left();
+        color.0 = flash.color;
+        color.0.set_a(alpha);
+    }
+}
@@ -1,15 +1,131 @@
 //! Renders a 2D scene containing a single, moving sprite.
 
-use std::{f32::consts::PI, time::Duration};
+// Bevy systems take their resources and queries as arguments
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
 
-use bevy::{prelude::*, sprite::Anchor};
+mod animation;
+mod assets;
+mod audio;
+mod collision;
+mod controls;
+mod focus;
+mod juice;
+mod particles;
+mod settings;
+mod storage;
+mod touch;
+
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+    time::Duration,
+};
+
+use animation::{
+    animate_sprites, despawn_finished, Animation, Clip, DespawnAfterClip, SpriteSheet,
+    SpriteSheetLoader,
+};
+use assets::GameAssets;
+use audio::{click_sounds, engine_sound, play_music, play_sfx, Mixer, PlaySfx, Sfx};
+use bevy::{
+    asset::{HandleId, LoadState},
+    ecs::system::EntityCommands,
+    prelude::*,
+    render::camera::{ScalingMode, Viewport},
+    sprite::Anchor,
+    window::{WindowFocused, WindowMode, WindowResized},
+};
+use collision::{collide, collide_swept, wrapped_delta, Placed, Shape, SpatialGrid};
+use controls::{update_actions, Action, Actions};
+use focus::{move_focus, Focused};
 use getrandom::getrandom;
+use juice::{apply_juice, calm_camera, fade_flashes, shake_camera, Juice, Trauma};
+use particles::{
+    emit_particles, setup_particles, update_particles, Burst, Emitter, ParticlePool, DEBRIS,
+    EXPLOSION, FLAME, IMPACT,
+};
 use serde::{Deserialize, Serialize};
+use settings::{Channel, Choice, Difficulty, Palette, Scaling, Settings};
+use touch::{
+    ask_player_name, layout_touch_overlay, setup_touch_overlay, update_touch_controls,
+    TouchControls,
+};
 use wasm_bindgen::prelude::*;
 
+/// Default playfield size, also the initial window size.
 const W_WIDTH: f32 = 1080.;
 const W_HEIGHT: f32 = 720.;
 
+/// Logical play area centered on the origin, whose opposite edges are joined.
+/// The camera scales it to the window, so it keeps its size whatever the
+/// window looks like.
+struct Playfield {
+    size: Vec2,
+}
+
+impl Default for Playfield {
+    fn default() -> Self {
+        Playfield {
+            size: Vec2::new(W_WIDTH, W_HEIGHT),
+        }
+    }
+}
+
+impl Playfield {
+    fn half(&self) -> Vec2 {
+        self.size / 2.
+    }
+
+    /// Copy of `to` across the wrapping edges that is closest to `from`.
+    fn nearest(&self, from: Vec2, to: Vec2) -> Vec2 {
+        from + wrapped_delta(from, to, self.size)
+    }
+}
+
+/// Time as seen by gameplay, which can run slower than the real time that
+/// menus keep using.
+struct GameTime {
+    /// 1 is real time, 0 freezes the game.
+    scale: f32,
+    /// Real seconds left before the game moves again, see [`GameTime::freeze`].
+    frozen: f32,
+    delta: Duration,
+}
+
+impl Default for GameTime {
+    fn default() -> Self {
+        GameTime {
+            scale: 1.,
+            frozen: 0.,
+            delta: Duration::ZERO,
+        }
+    }
+}
+
+impl GameTime {
+    fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Stops the game for `seconds` of real time, for a hit to sink in.
+    fn freeze(&mut self, seconds: f32) {
+        self.frozen = self.frozen.max(seconds);
+    }
+}
+
+fn tick_game_time(time: Res<Time>, mut game_time: ResMut<GameTime>) {
+    if game_time.frozen > 0. {
+        game_time.frozen -= time.delta_seconds();
+        game_time.delta = Duration::ZERO;
+    } else {
+        game_time.delta = time.delta().mul_f32(game_time.scale);
+    }
+}
+
 fn get_randu64() -> u64 {
     let result: u64 = unsafe {
         let mut data = [0u8; 8];
@@ -42,9 +158,14 @@ fn get_rand01() -> f64 {
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum AppState {
+    /// Waits for the [`GameAssets`] before the first screen shows up.
+    Loading,
     Start,
     InGame,
     GameOver,
+    Settings,
+    /// Pushed on top of `InGame`, which keeps its entities but stops updating.
+    Paused,
 }
 
 fn main() {
@@ -53,75 +174,557 @@ fn main() {
             title: "PPOid".to_string(),
             width: W_WIDTH,
             height: W_HEIGHT,
-            resizable: false,
+            resizable: true,
+            // Follow the page or iframe the game is embedded in
+            fit_canvas_to_parent: true,
             ..default()
         })
+        .insert_resource(ClearColor(Color::BLACK))
         .add_plugins(DefaultPlugins)
-        .add_state(AppState::Start)
+        .add_asset::<SpriteSheet>()
+        .init_asset_loader::<SpriteSheetLoader>()
+        .init_resource::<Playfield>()
+        .init_resource::<GameTime>()
+        .insert_resource(Settings::load())
+        .init_resource::<Actions>()
+        .init_resource::<TouchControls>()
+        .init_resource::<GameAssets>()
+        .init_resource::<Mixer>()
+        .init_resource::<ParticlePool>()
+        .init_resource::<Trauma>()
+        .init_resource::<SpentBullets>()
+        .add_event::<PlaySfx>()
+        .add_event::<Burst>()
+        .add_event::<Juice>()
+        .insert_resource(Rebinding(None))
+        .add_state(AppState::Loading)
         .add_startup_system(setup)
+        .add_system_to_stage(CoreStage::PreUpdate, tick_game_time)
+        .add_system(update_actions)
+        .add_system(fit_camera)
+        .add_system(apply_fullscreen)
+        .add_system(update_touch_controls.after(update_actions))
+        .add_system(click_sounds)
+        .add_system(play_sfx.after(click_sounds))
+        .add_system(play_music)
+        .add_system(engine_sound.after(update_touch_controls))
+        .add_system(apply_juice)
+        .add_system(shake_camera.after(apply_juice))
+        .add_system(fade_flashes)
+        .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(setup_loading))
+        .add_system_set(SystemSet::on_update(AppState::Loading).with_system(update_loading))
+        .add_system_set(SystemSet::on_exit(AppState::Loading).with_system(teardown_all))
         .add_system_set(SystemSet::on_enter(AppState::Start).with_system(setup_start))
         .add_system_set(
             SystemSet::on_update(AppState::Start)
+                .with_system(move_focus)
                 .with_system(update_start)
-                .with_system(update_player_name),
+                .with_system(update_player_name)
+                .with_system(prompt_player_name.after(update_touch_controls)),
         )
         .add_system_set(SystemSet::on_exit(AppState::Start).with_system(teardown_all))
-        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup_game))
+        .add_system_set(
+            SystemSet::on_enter(AppState::InGame)
+                .with_system(setup_game)
+                .with_system(setup_particles)
+                .with_system(setup_touch_overlay),
+        )
         .add_system_set(
             SystemSet::on_update(AppState::InGame)
-                .with_system(sprite_movement)
-                .with_system(move_block.before(sprite_movement))
+                .with_system(integrate_motion)
+                .with_system(
+                    move_block
+                        .after(update_touch_controls)
+                        .before(integrate_motion),
+                )
+                .with_system(enemy_behavior.before(integrate_motion))
+                .with_system(boss_behavior.before(integrate_motion))
                 .with_system(enemy_spawner.after(move_block))
-                .with_system(warp_system.after(enemy_spawner))
-                .with_system(bullet_hits.after(warp_system))
-                .with_system(player_hits.after(bullet_hits))
-                .with_system(cleanup.after(player_hits)),
+                .with_system(boss_spawner.after(move_block))
+                .with_system(warp_system.after(enemy_spawner).after(integrate_motion))
+                .with_system(update_enemy_grid.after(warp_system))
+                .with_system(enemy_clips.after(bullet_hits))
+                .with_system(animate_sprites.after(move_block).after(enemy_clips))
+                .with_system(despawn_finished.after(animate_sprites))
+                .with_system(update_ghosts::<Sprite, Handle<Image>>.after(warp_system))
+                .with_system(
+                    update_ghosts::<TextureAtlasSprite, Handle<TextureAtlas>>
+                        .after(warp_system)
+                        .after(animate_sprites),
+                )
+                .with_system(enemy_bounces.after(update_enemy_grid))
+                .with_system(bullet_hits.after(enemy_bounces))
+                .with_system(boss_hits.after(bullet_hits))
+                .with_system(boss_hud.after(boss_hits))
+                .with_system(player_hits.after(boss_hits))
+                .with_system(cleanup.after(player_hits))
+                .with_system(bullet_range.after(player_hits))
+                .with_system(death_sequence.after(player_hits))
+                .with_system(emit_particles.after(player_hits))
+                .with_system(update_particles.after(emit_particles))
+                .with_system(layout_touch_overlay.after(update_touch_controls))
+                .with_system(pause_game),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::InGame)
+                .with_system(teardown_all)
+                .with_system(end_death_sequence)
+                .with_system(calm_camera),
         )
-        .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(teardown_all))
         .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(setup_game_over))
-        .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(update_start))
+        .add_system_set(
+            SystemSet::on_update(AppState::GameOver)
+                .with_system(move_focus)
+                .with_system(update_start),
+        )
         .add_system_set(SystemSet::on_exit(AppState::GameOver).with_system(teardown_all))
+        .add_system_set(SystemSet::on_enter(AppState::Settings).with_system(setup_settings))
+        .add_system_set(
+            SystemSet::on_update(AppState::Settings)
+                .with_system(move_focus)
+                .with_system(update_settings)
+                .with_system(drag_sliders)
+                .with_system(rebind_key)
+                .with_system(
+                    update_settings_texts
+                        .after(update_settings)
+                        .after(drag_sliders)
+                        .after(rebind_key),
+                ),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Settings)
+                .with_system(teardown_all)
+                .with_system(save_settings),
+        )
+        .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(setup_pause_menu))
+        .add_system_set(
+            SystemSet::on_update(AppState::Paused)
+                .with_system(move_focus)
+                .with_system(update_pause_menu),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::Paused).with_system(teardown_pause_menu))
         .run();
 }
 
-#[derive(Component, Default)]
-struct Movment {
-    speed: f32,
-    heading: f32,
-    look_direction: f32,
+/// Unit vector pointing along `angle`, 0 being up and positive angles turning left.
+fn direction(angle: f32) -> Vec2 {
+    Vec2::new(-angle.sin(), angle.cos())
+}
+
+/// Angle of `v` as understood by [`direction`].
+fn angle_of(v: Vec2) -> f32 {
+    (-v.x).atan2(v.y)
 }
 
-impl Movment {
-    fn fill_rand(self: &mut Self) {
-        self.speed = get_rand01() as f32 * 300. + 200.;
-        self.heading = get_rand01() as f32 * PI * 2.;
-        self.look_direction = self.heading;
+/// Displacement per second, applied by `integrate_motion`.
+#[derive(Component, Default, Clone, Copy)]
+struct Velocity(Vec2);
+
+impl Velocity {
+    /// Random direction, speed picked from the `(min, max)` range.
+    fn random((min_speed, max_speed): (f32, f32)) -> Self {
+        let speed = get_rand01() as f32 * (max_speed - min_speed) + min_speed;
+        Velocity(direction(get_rand01() as f32 * PI * 2.) * speed)
+    }
+
+    fn heading(&self) -> f32 {
+        angle_of(self.0)
+    }
+
+    /// Turns toward `target` by at most `max_turn` radians, keeping the speed.
+    fn steer(&mut self, target: f32, max_turn: f32) {
+        self.0 = direction(turn_toward(self.heading(), target, max_turn)) * self.0.length();
     }
 }
 
+/// Turn rate of the [`Facing`], in radians per second.
+#[derive(Component, Default)]
+struct AngularVelocity(f32);
+
+/// Where the sprite points, independently of where it flies.
+#[derive(Component, Default)]
+struct Facing(f32);
+
+/// Speed lost per second.
+#[derive(Component)]
+struct Drag(f32);
+
+#[derive(Component)]
+struct MaxSpeed(f32);
+
 #[derive(Component)]
 struct Player(Timer, Timer);
 
+/// Player outline, narrowed toward the nose.
+static PLAYER_SHAPE: Shape = Shape::Polygon(&[
+    Vec2::new(12., 37.5),
+    Vec2::new(-12., 37.5),
+    Vec2::new(-21., 20.),
+    Vec2::new(-21., -37.5),
+    Vec2::new(21., -37.5),
+    Vec2::new(21., 20.),
+]);
+
 #[derive(Component)]
 struct Nowarp;
 
+/// Copy of a sprite drawn across the screen edge while its source overlaps it.
+#[derive(Component)]
+struct Ghost {
+    source: Entity,
+    /// Which edges the copy is moved across, each axis in `-1..=1`.
+    side: IVec2,
+}
+
 #[derive(Component)]
-struct Enemy(u32);
+struct Enemy {
+    kind: EnemyKind,
+    health: u32,
+}
 
 impl Enemy {
-    fn get_asset_path(health: u32) -> &'static str {
-        match health {
-            2 => "enemy-big.png",
-            1 => "enemy-medium.png",
-            0 => "enemy-small.png",
-            _ => "",
+    fn new(kind: EnemyKind) -> Self {
+        Enemy {
+            kind,
+            health: kind.def().health,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum EnemyKind {
+    Big,
+    Medium,
+    Small,
+    Seeker,
+    Shooter,
+    Orbiter,
+    ZigZag,
+}
+
+/// Everything that differs between enemy types.
+struct EnemyDef {
+    /// Sprite sheet file, see the `animation` module.
+    sheet: &'static str,
+    color: Color,
+    /// Replaces `color` with the colorblind palette.
+    colorblind_color: Color,
+    /// Size of the sprite on screen.
+    size: Vec2,
+    /// Outline used for collisions, centered on the sprite.
+    hitbox: Shape,
+    /// Bullet hits needed to destroy the enemy.
+    health: u32,
+    /// Range the initial speed is picked from.
+    speed: (f32, f32),
+    /// Points awarded for destroying the enemy.
+    score: u64,
+    /// How many enemies of which kind are left behind when destroyed.
+    split: Option<(u32, EnemyKind)>,
+    behavior: BehaviorKind,
+}
+
+static BIG_ENEMY: EnemyDef = EnemyDef {
+    sheet: "sheets/enemy-big.sheet.json",
+    color: Color::GOLD,
+    colorblind_color: Color::rgb(0.94, 0.89, 0.26),
+    size: Vec2::new(63., 90.),
+    hitbox: Shape::Rect(Vec2::new(56., 80.)),
+    health: 3,
+    speed: (100., 250.),
+    score: 100,
+    split: Some((2, EnemyKind::Medium)),
+    behavior: BehaviorKind::Drift,
+};
+
+static MEDIUM_ENEMY: EnemyDef = EnemyDef {
+    sheet: "sheets/enemy-medium.sheet.json",
+    color: Color::GOLD,
+    colorblind_color: Color::rgb(0.94, 0.89, 0.26),
+    size: Vec2::new(45., 64.),
+    hitbox: Shape::Rect(Vec2::new(40., 57.)),
+    health: 2,
+    speed: (150., 350.),
+    score: 150,
+    split: Some((2, EnemyKind::Small)),
+    behavior: BehaviorKind::Drift,
+};
+
+static SMALL_ENEMY: EnemyDef = EnemyDef {
+    sheet: "sheets/enemy-small.sheet.json",
+    color: Color::GOLD,
+    colorblind_color: Color::rgb(0.94, 0.89, 0.26),
+    size: Vec2::new(30., 43.),
+    hitbox: Shape::Rect(Vec2::new(27., 38.)),
+    health: 1,
+    speed: (250., 500.),
+    score: 200,
+    split: None,
+    behavior: BehaviorKind::Drift,
+};
+
+static SEEKER_ENEMY: EnemyDef = EnemyDef {
+    sheet: "sheets/enemy-small.sheet.json",
+    color: Color::ORANGE_RED,
+    colorblind_color: Color::rgb(0.84, 0.37, 0.),
+    size: Vec2::new(30., 43.),
+    hitbox: Shape::Rect(Vec2::new(27., 38.)),
+    health: 1,
+    speed: (140., 190.),
+    score: 250,
+    split: None,
+    behavior: BehaviorKind::Seek { turn_rate: 1.5 },
+};
+
+static SHOOTER_ENEMY: EnemyDef = EnemyDef {
+    sheet: "sheets/enemy-medium.sheet.json",
+    color: Color::CYAN,
+    colorblind_color: Color::rgb(0.34, 0.71, 0.91),
+    size: Vec2::new(45., 64.),
+    hitbox: Shape::Rect(Vec2::new(40., 57.)),
+    health: 2,
+    speed: (120., 160.),
+    score: 300,
+    split: None,
+    behavior: BehaviorKind::Shoot {
+        range: 300.,
+        reload: 1.5,
+    },
+};
+
+static ORBITER_ENEMY: EnemyDef = EnemyDef {
+    sheet: "sheets/enemy-small.sheet.json",
+    color: Color::VIOLET,
+    colorblind_color: Color::rgb(0.8, 0.47, 0.65),
+    size: Vec2::new(30., 43.),
+    hitbox: Shape::Rect(Vec2::new(27., 38.)),
+    health: 1,
+    speed: (220., 280.),
+    score: 250,
+    split: None,
+    behavior: BehaviorKind::Orbit { radius: 220. },
+};
+
+static ZIGZAG_ENEMY: EnemyDef = EnemyDef {
+    sheet: "sheets/enemy-medium.sheet.json",
+    color: Color::LIME_GREEN,
+    colorblind_color: Color::rgb(0., 0.62, 0.45),
+    size: Vec2::new(45., 64.),
+    hitbox: Shape::Rect(Vec2::new(40., 57.)),
+    health: 2,
+    speed: (200., 300.),
+    score: 200,
+    split: Some((2, EnemyKind::Small)),
+    behavior: BehaviorKind::ZigZag {
+        amplitude: PI / 4.,
+        frequency: 0.75,
+    },
+};
+
+impl EnemyDef {
+    /// Mass used when enemies bounce off each other, grows with the sprite area.
+    fn mass(&self) -> f32 {
+        self.size.x * self.size.y
+    }
+
+    fn color(&self, palette: Palette) -> Color {
+        match palette {
+            Palette::Standard => self.color,
+            Palette::Colorblind => self.colorblind_color,
+        }
+    }
+
+    /// Initial velocity, scaled for the difficulty.
+    fn random_velocity(&self, difficulty: Difficulty) -> Velocity {
+        let Velocity(v) = Velocity::random(self.speed);
+        Velocity(v * difficulty.enemy_speed())
+    }
+}
+
+impl EnemyKind {
+    const ALL: [EnemyKind; 7] = [
+        EnemyKind::Big,
+        EnemyKind::Medium,
+        EnemyKind::Small,
+        EnemyKind::Seeker,
+        EnemyKind::Shooter,
+        EnemyKind::Orbiter,
+        EnemyKind::ZigZag,
+    ];
+
+    fn def(self) -> &'static EnemyDef {
+        match self {
+            EnemyKind::Big => &BIG_ENEMY,
+            EnemyKind::Medium => &MEDIUM_ENEMY,
+            EnemyKind::Small => &SMALL_ENEMY,
+            EnemyKind::Seeker => &SEEKER_ENEMY,
+            EnemyKind::Shooter => &SHOOTER_ENEMY,
+            EnemyKind::Orbiter => &ORBITER_ENEMY,
+            EnemyKind::ZigZag => &ZIGZAG_ENEMY,
+        }
+    }
+
+    /// Picks the kind of the next periodically spawned enemy.
+    fn random() -> Self {
+        match get_rand01() {
+            r if r < 0.5 => EnemyKind::Big,
+            r if r < 0.65 => EnemyKind::Seeker,
+            r if r < 0.8 => EnemyKind::ZigZag,
+            r if r < 0.9 => EnemyKind::Orbiter,
+            _ => EnemyKind::Shooter,
+        }
+    }
+}
+
+/// How an enemy steers, as configured in its [`EnemyDef`].
+#[derive(Clone, Copy)]
+enum BehaviorKind {
+    /// Keeps flying in a straight line.
+    Drift,
+    /// Turns toward the player by at most `turn_rate` radians per second.
+    Seek { turn_rate: f32 },
+    /// Closes in to `range` and then circles the player, firing every `reload` seconds.
+    Shoot { range: f32, reload: f32 },
+    /// Circles the player at roughly `radius`.
+    Orbit { radius: f32 },
+    /// Weaves by up to `amplitude` radians around its initial heading.
+    ZigZag { amplitude: f32, frequency: f32 },
+}
+
+/// Per-entity state of an enemy behavior, driven by `enemy_behavior`.
+#[derive(Component)]
+struct Behavior {
+    kind: BehaviorKind,
+    base_heading: f32,
+    elapsed: f32,
+    cooldown: f32,
+}
+
+impl Behavior {
+    fn new(kind: BehaviorKind, velocity: &Velocity) -> Self {
+        let cooldown = match kind {
+            BehaviorKind::Shoot { reload, .. } => reload,
+            _ => 0.,
+        };
+        Behavior {
+            kind,
+            base_heading: velocity.heading(),
+            elapsed: 0.,
+            cooldown,
         }
     }
 }
 
+/// Shot fired by an enemy. It kills the player and, with
+/// [`Settings::enemy_friendly_fire`], damages enemies other than its owner.
+#[derive(Component)]
+struct EnemyBullet {
+    owner: Entity,
+}
+
+/// Shot fired by the player.
 #[derive(Component)]
 struct Bullet;
 
+/// Bullets that hit something this frame. Their despawn only applies at the end
+/// of the stage, so the hit tests that run later skip them.
+#[derive(Default)]
+struct SpentBullets(HashSet<Entity>);
+
+static BULLET_SHAPE: Shape = Shape::Rect(Vec2::new(5., 17.));
+
+/// How a weapon fires, see [`Weapon`].
+struct WeaponDef {
+    /// Bullet speed relative to the ship.
+    speed: f32,
+    /// Seconds between two shots.
+    cooldown: f32,
+    /// Share of the ship velocity added to the bullet, 1 keeps full momentum.
+    inherit: f32,
+    /// Speed the ship loses backward on every shot, 0 for none.
+    recoil: f32,
+}
+
+static BLASTER: WeaponDef = WeaponDef {
+    speed: 700.,
+    cooldown: 0.2,
+    inherit: 1.,
+    recoil: 15.,
+};
+
+/// Weapon carried by the player.
+#[derive(Component)]
+struct Weapon(&'static WeaponDef);
+
+/// Score needed for the first boss to show up, and score gap between bosses.
+const BOSS_EVERY: u64 = 5000;
+/// Points awarded for destroying a boss.
+const BOSS_SCORE: u64 = 5000;
+/// Distance from the boss center beyond which none of its parts can be hit.
+const BOSS_REACH: f32 = 250.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BossPhase {
+    /// Turrets are up and shield the core.
+    Escort,
+    /// Turrets are gone, the core chases the player firing bursts.
+    Exposed,
+    /// Core is below half health and fights harder.
+    Enraged,
+}
+
+/// One hitbox of a boss, placed relative to the boss center.
+struct BossPart {
+    offset: Vec2,
+    shape: Shape,
+    health: u32,
+    max_health: u32,
+    core: bool,
+    /// Entity drawing the part.
+    sprite: Entity,
+}
+
+#[derive(Component)]
+struct Boss {
+    parts: Vec<BossPart>,
+    phase: BossPhase,
+    cooldown: f32,
+    elapsed: f32,
+}
+
+impl BossPart {
+    fn placed(&self, boss: Vec2, rotation: f32) -> Placed<'_> {
+        let position = boss + Vec2::from_angle(rotation).rotate(self.offset);
+        self.shape.at(position, rotation)
+    }
+}
+
+impl Boss {
+    fn is_dead(&self) -> bool {
+        self.parts.iter().all(|p| p.health == 0)
+    }
+
+    fn health_ratio(&self) -> f32 {
+        let (health, max) = self
+            .parts
+            .iter()
+            .fold((0, 0), |(h, m), p| (h + p.health, m + p.max_health));
+        health as f32 / max as f32
+    }
+}
+
+/// Boss health bar frame.
+#[derive(Component)]
+struct BossHud;
+
+#[derive(Component)]
+struct BossHealthBar;
+
+/// Distance a wrapping bullet may still travel before it expires.
+#[derive(Component)]
+struct Range(f32);
+
 #[derive(Component)]
 struct Background;
 
@@ -134,6 +737,39 @@ struct PlayerName;
 #[derive(Hash)]
 struct PlayerNameText(String);
 
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum MenuButton {
+    Play,
+    Settings,
+    Reset,
+    Back,
+    Resume,
+    Restart,
+    Quit,
+}
+
+#[derive(Component)]
+struct PauseMenu;
+
+/// Settings screen button showing and changing the keys of an action.
+#[derive(Component)]
+struct BindingButton(Action);
+
+/// Settings screen button cycling through the values of a setting.
+#[derive(Component)]
+struct ChoiceButton(Choice);
+
+/// Settings screen volume bar, set by clicking or dragging along it.
+#[derive(Component)]
+struct VolumeSlider(Channel);
+
+/// Filled part of a [`VolumeSlider`].
+#[derive(Component)]
+struct SliderFill(Channel);
+
+/// Action waiting for its new key in the settings screen.
+struct Rebinding(Option<Action>);
+
 struct Score(u64);
 
 impl Score {
@@ -146,12 +782,159 @@ impl Score {
     }
 }
 
-fn setup(mut commands: Commands) {
-    commands.spawn_bundle(Camera2dBundle::default());
-    commands.insert_resource(Score(0))
+/// Score at which the next boss is spawned.
+struct NextBoss(u64);
+
+/// Real time left between the player blowing up and the game over screen,
+/// during which the field stays up in slow motion.
+struct DeathSequence(Timer);
+
+const DEATH_DURATION: f32 = 2.5;
+
+/// Enemies filed by position, rebuilt every frame before hits are checked.
+struct EnemyGrid(SpatialGrid<Entity>);
+
+fn setup(mut commands: Commands, playfield: Res<Playfield>) {
+    let mut camera = Camera2dBundle::default();
+    camera.projection.scaling_mode = ScalingMode::Auto {
+        min_width: playfield.size.x,
+        min_height: playfield.size.y,
+    };
+    commands.spawn_bundle(camera);
+    commands.insert_resource(Score(0));
+    commands.insert_resource(NextBoss(BOSS_EVERY));
+    commands.insert_resource(EnemyGrid(SpatialGrid::new(playfield.size, 60.)))
+}
+
+/// Keeps the camera viewport around the playfield when letterboxing.
+fn fit_camera(
+    mut resized: EventReader<WindowResized>,
+    windows: Res<Windows>,
+    playfield: Res<Playfield>,
+    settings: Res<Settings>,
+    mut cameras: Query<&mut Camera, With<Camera2d>>,
+) {
+    if resized.iter().count() == 0 && !settings.is_changed() {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let physical = UVec2::new(window.physical_width(), window.physical_height());
+
+    let viewport = match settings.scaling {
+        Scaling::Fit => None,
+        Scaling::Letterbox => {
+            let scale = (physical.as_vec2() / playfield.size).min_element();
+            let size = (playfield.size * scale)
+                .as_uvec2()
+                .max(UVec2::ONE)
+                .min(physical);
+            Some(Viewport {
+                physical_position: (physical - size) / 2,
+                physical_size: size,
+                ..default()
+            })
+        }
+    };
+    for mut camera in &mut cameras {
+        camera.viewport = viewport.clone();
+    }
+}
+
+/// Filled part of the loading screen progress bar.
+#[derive(Component)]
+struct LoadingBar;
+
+fn setup_loading(mut commands: Commands, assets: Res<GameAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                // Reversed so that rows go from top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|builder| {
+            builder.spawn_bundle(TextBundle::from_section(
+                "Loading...",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    font: assets.font.clone(),
+                },
+            ));
+            builder
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.), Val::Px(20.)),
+                        margin: UiRect::all(Val::Px(16.)),
+                        ..default()
+                    },
+                    color: Color::DARK_GRAY.into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                            ..default()
+                        },
+                        color: Color::AQUAMARINE.into(),
+                        ..default()
+                    })
+                    .insert(LoadingBar);
+                });
+        });
+}
+
+/// Fills the progress bar and moves on once every [`GameAssets`] handle is
+/// in, which keeps anything from popping in later on the web where downloads
+/// are slow. Assets that failed to load count as done, the game goes on
+/// without them.
+fn update_loading(
+    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
+    sheets: Res<Assets<SpriteSheet>>,
+    mut state: ResMut<State<AppState>>,
+    mut bars: Query<&mut Style, With<LoadingBar>>,
+) {
+    let finished = |id: HandleId| {
+        matches!(
+            asset_server.get_load_state(id),
+            LoadState::Loaded | LoadState::Failed
+        )
+    };
+    let handles = assets.handles();
+    let total = handles.len() + assets.sheets().count();
+    let mut done = handles.into_iter().filter(|id| finished(*id)).count();
+    // Sheet images are requested by the sheets themselves
+    done += assets
+        .sheets()
+        .filter(|handle| match sheets.get(handle) {
+            Some(sheet) => finished(sheet.image().id),
+            None => asset_server.get_load_state(handle.id) == LoadState::Failed,
+        })
+        .count();
+
+    for mut style in &mut bars {
+        style.size.width = Val::Percent(done as f32 * 100. / total as f32);
+    }
+    if done == total {
+        let _ = state.set(AppState::Start);
+    }
 }
 
-fn setup_start(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_start(mut commands: Commands, assets: Res<GameAssets>, name: Option<Res<PlayerNameText>>) {
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -182,16 +965,18 @@ fn setup_start(mut commands: Commands, asset_server: Res<AssetServer>) {
                     color: Color::AQUAMARINE.into(),
                     ..default()
                 })
+                .insert(MenuButton::Play)
                 .with_children(|child| {
                     child.spawn_bundle(TextBundle::from_section(
                         "Start!",
                         TextStyle {
                             font_size: 40.0,
                             color: Color::DARK_GRAY.into(),
-                            font: asset_server.load("FiraSans-Bold.ttf"),
+                            font: assets.font.clone(),
                         },
                     ));
                 });
+            spawn_menu_button(builder, &assets, "Settings", 200., MenuButton::Settings);
         })
         .with_children(|builder| {
             builder
@@ -201,41 +986,68 @@ fn setup_start(mut commands: Commands, asset_server: Res<AssetServer>) {
                         TextStyle {
                             font_size: 36.0,
                             color: Color::DARK_GRAY.into(),
-                            font: asset_server.load("FiraSans-Bold.ttf"),
+                            font: assets.font.clone(),
                         },
                     ),
                     TextSection::new(
-                        "",
+                        name.map_or(String::new(), |n| n.0.clone()),
                         TextStyle {
                             font_size: 36.0,
                             color: Color::DARK_GRAY.into(),
-                            font: asset_server.load("FiraSans-Bold.ttf"),
+                            font: assets.font.clone(),
                         },
                     ),
                 ]))
-                .insert(PlayerName);
+                .insert(PlayerName)
+                .insert(Interaction::default());
         });
 }
 
 fn update_start(
     mut commands: Commands,
     mut state: ResMut<State<AppState>>,
-    interaction: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    interaction: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    focused: Query<&MenuButton, With<Focused>>,
     player_name_queue: Query<&Text, With<PlayerName>>,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<Input<GamepadButton>>,
 ) {
-    for int in &interaction {
-        match *int {
-            Interaction::Clicked => {
-                if !player_name_queue.is_empty() {
-                    commands.insert_resource(PlayerNameText(
-                        player_name_queue.single().sections[1].value.clone(),
-                    ));
-                }
-                state.set(AppState::InGame).unwrap();
-            }
-            _ => (),
-        }
+    let mut clicked = interaction
+        .iter()
+        .find(|(i, _)| **i == Interaction::Clicked)
+        .map(|(_, button)| *button);
+    // Without a focused button the gamepad goes straight for the play button
+    if any_gamepad_just_pressed(&gamepads, &pad_buttons, &[GamepadButtonType::South]) {
+        clicked = Some(focused.get_single().map_or(MenuButton::Play, |b| *b));
+    }
+    if any_gamepad_just_pressed(&gamepads, &pad_buttons, &[GamepadButtonType::Start]) {
+        clicked = Some(MenuButton::Play);
+    }
+    let next = match clicked {
+        Some(MenuButton::Play) => AppState::InGame,
+        Some(MenuButton::Settings) => AppState::Settings,
+        _ => return,
+    };
+
+    if !player_name_queue.is_empty() {
+        commands.insert_resource(PlayerNameText(
+            player_name_queue.single().sections[1].value.clone(),
+        ));
     }
+    state.set(next).unwrap();
+}
+
+/// Whether any connected gamepad just pressed one of `types`.
+fn any_gamepad_just_pressed(
+    gamepads: &Gamepads,
+    buttons: &Input<GamepadButton>,
+    types: &[GamepadButtonType],
+) -> bool {
+    gamepads.iter().any(|gamepad| {
+        types
+            .iter()
+            .any(|t| buttons.just_pressed(GamepadButton::new(*gamepad, *t)))
+    })
 }
 
 fn update_player_name(
@@ -262,9 +1074,29 @@ fn update_player_name(
     }
 }
 
+/// Tapping the name opens a prompt, touch screens have no keyboard for
+/// `update_player_name`.
+fn prompt_player_name(
+    controls: Res<TouchControls>,
+    mut names: Query<(&Interaction, &mut Text), (Changed<Interaction>, With<PlayerName>)>,
+) {
+    for (interaction, mut text) in &mut names {
+        if !controls.enabled || *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let Some(name) = ask_player_name(&text.sections[1].value) {
+            text.sections[1].value = name
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .take(16)
+                .collect();
+        }
+    }
+}
+
 fn setup_game_over(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
     score: Res<Score>,
     name: Res<PlayerNameText>,
 ) {
@@ -303,13 +1135,14 @@ fn setup_game_over(
                     color: Color::AQUAMARINE.into(),
                     ..default()
                 })
+                .insert(MenuButton::Play)
                 .with_children(|child| {
                     child.spawn_bundle(TextBundle::from_section(
                         "Play again!",
                         TextStyle {
                             font_size: 40.0,
                             color: Color::DARK_GRAY.into(),
-                            font: asset_server.load("FiraSans-Bold.ttf"),
+                            font: assets.font.clone(),
                         },
                     ));
                 });
@@ -327,7 +1160,7 @@ fn setup_game_over(
                     // Accepts a `String` or any type that converts into a `String`, such as `&str`
                     format!("Leaderboard: {}", leaderboard.as_str()),
                     TextStyle {
-                        font: asset_server.load("FiraSans-Bold.ttf"),
+                        font: assets.font.clone(),
                         font_size: 28.0,
                         color: Color::GOLD,
                     },
@@ -351,7 +1184,7 @@ fn setup_game_over(
                     // Accepts a `String` or any type that converts into a `String`, such as `&str`
                     fscore_text,
                     TextStyle {
-                        font: asset_server.load("FiraSans-Bold.ttf"),
+                        font: assets.font.clone(),
                         font_size: 40.0,
                         color: Color::WHITE,
                     },
@@ -370,93 +1203,584 @@ fn setup_game_over(
         });
 }
 
-fn teardown_all(mut commands: Commands, query: Query<Entity, Without<Camera2d>>) {
-    for e in &query {
-        commands.entity(e).despawn_recursive();
-    }
+fn spawn_menu_button(
+    parent: &mut ChildBuilder,
+    assets: &GameAssets,
+    label: &str,
+    width: f32,
+    button: impl Component,
+) {
+    spawn_button(parent, assets, label, Vec2::new(width, 50.), 8., button);
 }
 
-fn spawn_new_enemy(
-    commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    player_pos: &Transform,
+/// Smaller button for the rows of the settings screen.
+fn spawn_setting_button(
+    parent: &mut ChildBuilder,
+    assets: &GameAssets,
+    label: &str,
+    button: impl Component,
 ) {
-    let mut movement = Movment::default();
-    let distance = 200. + (get_rand01() * ((W_HEIGHT as f64).min(W_WIDTH as f64) - 400.));
-    let new_displaysment = Transform::from_translation(
-        (Vec2::from_angle((get_rand01() * 2. * PI as f64) as f32)
-            .rotate(Vec2::new(distance as f32, 0.)))
-        .extend(0.),
-    );
-    let mut new_transform = player_pos.clone().mul_transform(new_displaysment);
-    if new_transform.translation.x > W_WIDTH / 2. {
-        new_transform.translation.x -= W_WIDTH;
-    }
-    if new_transform.translation.x < -W_WIDTH / 2. {
-        new_transform.translation.x += W_WIDTH;
-    }
-    if new_transform.translation.y > W_HEIGHT / 2. {
-        new_transform.translation.y -= W_HEIGHT;
-    }
-    if new_transform.translation.y < -W_HEIGHT / 2. {
-        new_transform.translation.y += W_HEIGHT;
-    }
-    movement.fill_rand();
-    commands
-        .spawn_bundle(SpriteBundle {
-            texture: asset_server.load(Enemy::get_asset_path(2)),
-            sprite: Sprite {
-                color: Color::GOLD,
-                custom_size: Some(Vec2::new(35.0, 50.0)),
+    spawn_button(parent, assets, label, Vec2::new(200., 36.), 4., button);
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    assets: &GameAssets,
+    label: &str,
+    size: Vec2,
+    margin: f32,
+    button: impl Component,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+                margin: UiRect::all(Val::Px(margin)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
                 ..default()
             },
-            transform: new_transform,
+            color: Color::AQUAMARINE.into(),
             ..default()
         })
-        .insert(movement)
-        .insert(Enemy(2));
+        .insert(button)
+        .with_children(|child| {
+            child.spawn_bundle(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 28.0,
+                    color: Color::DARK_GRAY,
+                    font: assets.font.clone(),
+                },
+            ));
+        });
 }
 
-fn setup_game(mut commands: Commands, asset_server: Res<AssetServer>, mut score: ResMut<Score>) {
-    score.reset();
-    commands
-        .spawn_bundle(SpriteBundle {
-            texture: asset_server.load("bg.png"),
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(W_WIDTH, W_HEIGHT)),
+fn spawn_slider(parent: &mut ChildBuilder, channel: Channel, value: f32) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(200.), Val::Px(20.)),
+                margin: UiRect::all(Val::Px(8.0)),
                 ..default()
             },
-            transform: Transform::from_xyz(0., 0., 0.),
+            color: Color::DARK_GRAY.into(),
             ..default()
         })
-        .insert(Background);
-    commands.spawn_bundle(
-        // Create a TextBundle that has a Text with a single section.
+        .insert(VolumeSlider(channel))
+        .with_children(|track| {
+            track
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(value * 100.), Val::Percent(100.)),
+                        ..default()
+                    },
+                    color: Color::AQUAMARINE.into(),
+                    ..default()
+                })
+                .insert(SliderFill(channel));
+        });
+}
+
+fn setup_settings(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    rebinding.0 = None;
+    let text = |value: &str, size: f32, color: Color| {
         TextBundle::from_section(
-            // Accepts a `String` or any type that converts into a `String`, such as `&str`
-            format!("Score: {}", score.0),
+            value,
             TextStyle {
-                font: asset_server.load("FiraSans-Bold.ttf"),
-                font_size: 40.0,
-                color: Color::WHITE,
+                font_size: size,
+                color,
+                font: assets.font.clone(),
             },
-        ) // Set the alignment of the Text
-        .with_text_alignment(TextAlignment::TOP_CENTER)
-        // Set the style of the TextBundle itself.
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(5.0),
-                right: Val::Px(15.0),
+        )
+    };
+    let label = |value: &str| {
+        text(value, 24., Color::WHITE).with_style(Style {
+            size: Size::new(Val::Px(150.), Val::Auto),
+            ..default()
+        })
+    };
+    let row = || NodeBundle {
+        style: Style {
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: Color::NONE.into(),
+        ..default()
+    };
+    let column = || NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::ColumnReverse,
+            margin: UiRect::all(Val::Px(20.)),
+            ..default()
+        },
+        color: Color::NONE.into(),
+        ..default()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                // Reversed so that rows go from top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
                 ..default()
             },
+            color: Color::NONE.into(),
             ..default()
-        }),
+        })
+        .with_children(|builder| {
+            builder.spawn_bundle(text("Settings", 40., Color::WHITE));
+            builder.spawn_bundle(row()).with_children(|columns| {
+                columns.spawn_bundle(column()).with_children(|options| {
+                    for channel in Channel::ALL {
+                        options.spawn_bundle(row()).with_children(|row| {
+                            row.spawn_bundle(label(channel.label()));
+                            spawn_slider(row, channel, settings.volume(channel));
+                        });
+                    }
+                    for choice in Choice::DISPLAY {
+                        options.spawn_bundle(row()).with_children(|row| {
+                            row.spawn_bundle(label(choice.label()));
+                            spawn_setting_button(
+                                row,
+                                &assets,
+                                settings.describe(choice),
+                                ChoiceButton(choice),
+                            );
+                        });
+                    }
+                });
+                columns.spawn_bundle(column()).with_children(|controls| {
+                    for action in Action::ALL {
+                        controls.spawn_bundle(row()).with_children(|row| {
+                            row.spawn_bundle(label(action.label()));
+                            spawn_setting_button(
+                                row,
+                                &assets,
+                                &settings.bindings.describe(action),
+                                BindingButton(action),
+                            );
+                        });
+                    }
+                    for choice in Choice::GAMEPLAY {
+                        controls.spawn_bundle(row()).with_children(|row| {
+                            row.spawn_bundle(label(choice.label()));
+                            spawn_setting_button(
+                                row,
+                                &assets,
+                                settings.describe(choice),
+                                ChoiceButton(choice),
+                            );
+                        });
+                    }
+                });
+            });
+            builder.spawn_bundle(text(
+                "Click an action, then press its new key. Esc cancels.",
+                20.,
+                Color::GRAY,
+            ));
+            builder.spawn_bundle(row()).with_children(|row| {
+                spawn_menu_button(row, &assets, "Reset", 150., MenuButton::Reset);
+                spawn_menu_button(row, &assets, "Back", 150., MenuButton::Back);
+            });
+        });
+}
+
+fn update_settings(
+    mut state: ResMut<State<AppState>>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    menu: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    binding_buttons: Query<(&Interaction, &BindingButton), Changed<Interaction>>,
+    choice_buttons: Query<(&Interaction, &ChoiceButton), Changed<Interaction>>,
+    focused: Query<
+        (
+            Option<&MenuButton>,
+            Option<&BindingButton>,
+            Option<&ChoiceButton>,
+            Option<&VolumeSlider>,
+        ),
+        With<Focused>,
+    >,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<Input<GamepadButton>>,
+) {
+    for (interaction, BindingButton(action)) in &binding_buttons {
+        if *interaction == Interaction::Clicked {
+            rebinding.0 = Some(*action);
+        }
+    }
+    for (interaction, ChoiceButton(choice)) in &choice_buttons {
+        if *interaction == Interaction::Clicked {
+            settings.cycle(*choice);
+        }
+    }
+
+    let mut clicked = menu
+        .iter()
+        .find(|(i, _)| **i == Interaction::Clicked)
+        .map(|(_, button)| *button);
+    if any_gamepad_just_pressed(&gamepads, &pad_buttons, &[GamepadButtonType::South]) {
+        match focused.get_single() {
+            Ok((Some(button), ..)) => clicked = Some(*button),
+            Ok((_, Some(BindingButton(action)), ..)) => rebinding.0 = Some(*action),
+            Ok((_, _, Some(ChoiceButton(choice)), _)) => settings.cycle(*choice),
+            // Sliders step up by a tenth, wrapping around to silence
+            Ok((.., Some(VolumeSlider(channel)))) => {
+                let volume = settings.volume(*channel);
+                let next = if volume >= 1. { 0. } else { volume + 0.1 };
+                settings.set_volume(*channel, next);
+            }
+            _ => (),
+        }
+    }
+    if any_gamepad_just_pressed(&gamepads, &pad_buttons, &[GamepadButtonType::East]) {
+        clicked = Some(MenuButton::Back);
+    }
+    match clicked {
+        Some(MenuButton::Reset) => *settings = Settings::default(),
+        Some(MenuButton::Back) => state.set(AppState::Start).unwrap(),
+        _ => (),
+    }
+}
+
+/// Sets the volume of a slider held down with the mouse from the cursor position.
+fn drag_sliders(
+    windows: Res<Windows>,
+    mut settings: ResMut<Settings>,
+    sliders: Query<(&Interaction, &Node, &GlobalTransform, &VolumeSlider)>,
+) {
+    let cursor = match windows.get_primary().and_then(|w| w.cursor_position()) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    for (interaction, node, transform, VolumeSlider(channel)) in &sliders {
+        if *interaction == Interaction::Clicked {
+            let left = transform.translation().x - node.size.x / 2.;
+            settings.set_volume(*channel, (cursor.x - left) / node.size.x);
+        }
+    }
+}
+
+/// Binds the next key pressed to the action picked in the settings screen.
+fn rebind_key(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let action = match rebinding.0 {
+        Some(action) => action,
+        None => return,
+    };
+    if let Some(key) = keyboard_input.get_just_pressed().next() {
+        if *key != KeyCode::Escape {
+            settings.bindings.rebind(action, *key);
+        }
+        rebinding.0 = None;
+    }
+}
+
+fn update_settings_texts(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    bindings: Query<(&BindingButton, &Children)>,
+    choices: Query<(&ChoiceButton, &Children)>,
+    mut fills: Query<(&SliderFill, &mut Style)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    let mut set_label = |children: &Children, label: &str| {
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.to_string();
+            }
+        }
+    };
+
+    for (BindingButton(action), children) in &bindings {
+        if rebinding.0 == Some(*action) {
+            set_label(children, "Press a key...");
+        } else {
+            set_label(children, &settings.bindings.describe(*action));
+        }
+    }
+    for (ChoiceButton(choice), children) in &choices {
+        set_label(children, settings.describe(*choice));
+    }
+    for (SliderFill(channel), mut style) in &mut fills {
+        style.size.width = Val::Percent(settings.volume(*channel) * 100.);
+    }
+}
+
+/// Writes the settings to storage when leaving their screen, the only place
+/// they change, rather than on every frame a slider is dragged.
+fn save_settings(settings: Res<Settings>) {
+    settings.save();
+}
+
+fn apply_fullscreen(settings: Res<Settings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let mode = if settings.fullscreen {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+    if let Some(window) = windows.get_primary_mut() {
+        if window.mode() != mode {
+            window.set_mode(mode);
+        }
+    }
+}
+
+/// Pauses on Esc, P or the gamepad start button, and when the window loses focus.
+fn pause_game(
+    mut state: ResMut<State<AppState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut focus: EventReader<WindowFocused>,
+    gamepads: Res<Gamepads>,
+    mut pad_buttons: ResMut<Input<GamepadButton>>,
+) {
+    let lost_focus = focus.iter().any(|e| !e.focused);
+    if take_pause_input(&mut keyboard_input, &gamepads, &mut pad_buttons) || lost_focus {
+        // Fails when the run already ends this frame, which is fine
+        let _ = state.push(AppState::Paused);
+    }
+}
+
+/// Whether a pause toggle was just pressed. The press is consumed, so the
+/// state entered this frame does not toggle straight back.
+fn take_pause_input(
+    keyboard_input: &mut Input<KeyCode>,
+    gamepads: &Gamepads,
+    pad_buttons: &mut Input<GamepadButton>,
+) -> bool {
+    let mut pressed = false;
+    for key in [KeyCode::Escape, KeyCode::P] {
+        pressed |= keyboard_input.clear_just_pressed(key);
+    }
+    for gamepad in gamepads.iter() {
+        pressed |=
+            pad_buttons.clear_just_pressed(GamepadButton::new(*gamepad, GamepadButtonType::Start));
+    }
+    pressed
+}
+
+fn setup_pause_menu(mut commands: Commands, assets: Res<GameAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                position_type: PositionType::Absolute,
+                // Reversed so that buttons go from top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0., 0., 0., 0.6).into(),
+            ..default()
+        })
+        .insert(PauseMenu)
+        .with_children(|builder| {
+            builder.spawn_bundle(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    font: assets.font.clone(),
+                },
+            ));
+            spawn_menu_button(builder, &assets, "Resume", 220., MenuButton::Resume);
+            spawn_menu_button(builder, &assets, "Restart", 220., MenuButton::Restart);
+            spawn_menu_button(builder, &assets, "Quit to title", 220., MenuButton::Quit);
+        });
+}
+
+fn update_pause_menu(
+    mut state: ResMut<State<AppState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    interaction: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    focused: Query<&MenuButton, With<Focused>>,
+    gamepads: Res<Gamepads>,
+    mut pad_buttons: ResMut<Input<GamepadButton>>,
+) {
+    let mut clicked = interaction
+        .iter()
+        .find(|(i, _)| **i == Interaction::Clicked)
+        .map(|(_, button)| *button);
+    if any_gamepad_just_pressed(&gamepads, &pad_buttons, &[GamepadButtonType::South]) {
+        clicked = Some(focused.get_single().map_or(MenuButton::Resume, |b| *b));
+    }
+    if take_pause_input(&mut keyboard_input, &gamepads, &mut pad_buttons) {
+        clicked = Some(MenuButton::Resume);
+    }
+
+    // Replacing exits every state on the stack, `InGame` tears the run down
+    match clicked {
+        Some(MenuButton::Resume) => state.pop().unwrap(),
+        Some(MenuButton::Restart) => state.replace(AppState::InGame).unwrap(),
+        Some(MenuButton::Quit) => state.replace(AppState::Start).unwrap(),
+        _ => (),
+    }
+}
+
+fn teardown_pause_menu(mut commands: Commands, query: Query<Entity, With<PauseMenu>>) {
+    for e in &query {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+fn teardown_all(mut commands: Commands, query: Query<Entity, Without<Camera2d>>) {
+    for e in &query {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+/// Random position around the player, not too close to it.
+fn spawn_position(playfield: &Playfield, player_pos: &Transform) -> Transform {
+    let half = playfield.half();
+    let distance = 200. + (get_rand01() * (playfield.size.min_element() as f64 - 400.));
+    let new_displaysment = Transform::from_translation(
+        (Vec2::from_angle((get_rand01() * 2. * PI as f64) as f32)
+            .rotate(Vec2::new(distance as f32, 0.)))
+        .extend(0.),
     );
+    let mut new_transform = player_pos.clone().mul_transform(new_displaysment);
+    if new_transform.translation.x > half.x {
+        new_transform.translation.x -= playfield.size.x;
+    }
+    if new_transform.translation.x < -half.x {
+        new_transform.translation.x += playfield.size.x;
+    }
+    if new_transform.translation.y > half.y {
+        new_transform.translation.y -= playfield.size.y;
+    }
+    if new_transform.translation.y < -half.y {
+        new_transform.translation.y += playfield.size.y;
+    }
+    new_transform
+}
+
+/// Spawns an enemy of `kind` heading off in a random direction.
+fn spawn_enemy(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    settings: &Settings,
+    kind: EnemyKind,
+    transform: Transform,
+) {
+    let def = kind.def();
+    let velocity = def.random_velocity(settings.difficulty);
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                color: def.color(settings.palette),
+                custom_size: Some(def.size),
+                ..default()
+            },
+            transform,
+            ..default()
+        })
+        .insert(Animation::new(assets.enemy(kind)))
+        .insert(Behavior::new(def.behavior, &velocity))
+        .insert(Facing(velocity.heading()))
+        .insert(velocity)
+        .insert(Enemy::new(kind));
+}
+
+/// Spawns a bullet pointing along `facing`, for the caller to mark as the
+/// player's or an enemy's.
+fn spawn_bullet<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    assets: &GameAssets,
+    color: Color,
+    position: Vec3,
+    facing: f32,
+    velocity: Vec2,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut bullet = commands.spawn_bundle(SpriteBundle {
+        texture: assets.bullet.clone(),
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::new(5.0, 17.0)),
+            ..default()
+        },
+        transform: Transform::from_translation(position)
+            .with_rotation(Quat::from_rotation_z(facing)),
+        ..default()
+    });
+    bullet.insert(Velocity(velocity)).insert(Facing(facing));
+    bullet
+}
+
+fn setup_game(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    playfield: Res<Playfield>,
+    settings: Res<Settings>,
+    mut score: ResMut<Score>,
+    mut next_boss: ResMut<NextBoss>,
+) {
+    score.reset();
+    next_boss.0 = BOSS_EVERY;
     commands
         .spawn_bundle(SpriteBundle {
-            texture: asset_server.load("patron.png"),
+            texture: assets.background.clone(),
             sprite: Sprite {
+                custom_size: Some(playfield.size),
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., 0.),
+            ..default()
+        })
+        .insert(Background);
+    commands
+        .spawn_bundle(
+            // Create a TextBundle that has a Text with a single section.
+            TextBundle::from_section(
+                // Accepts a `String` or any type that converts into a `String`, such as `&str`
+                format!("Score: {}", score.0),
+                TextStyle {
+                    font: assets.font.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ) // Set the alignment of the Text
+            .with_text_alignment(TextAlignment::TOP_CENTER)
+            // Set the style of the TextBundle itself.
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ScoreText);
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
                 custom_size: Some(Vec2::new(42.0, 75.0)),
                 anchor: Anchor::TopCenter,
                 ..default()
@@ -464,126 +1788,347 @@ fn setup_game(mut commands: Commands, asset_server: Res<AssetServer>, mut score:
             transform: Transform::from_xyz(0., 0., 1.),
             ..default()
         })
-        .insert(Movment::default())
+        .insert(Animation::new(assets.ship.clone()))
+        .insert(Velocity::default())
+        .insert(Facing(0.))
+        .insert(AngularVelocity(0.))
+        .insert(Drag(125.))
+        .insert(MaxSpeed(500.))
+        .insert(Weapon(&BLASTER))
+        // Flame from the back of the ship, which hangs below its anchor
+        .insert(Emitter::new(&FLAME, Vec2::new(0., -75.), PI))
         .insert(Player(
-            Timer::from_seconds(0.2, false)
-                .tick(Duration::from_secs_f32(0.2))
+            Timer::from_seconds(BLASTER.cooldown, false)
+                .tick(Duration::from_secs_f32(BLASTER.cooldown))
                 .to_owned(),
-            Timer::from_seconds(10., false),
+            Timer::from_seconds(settings.difficulty.spawn_interval(), false),
         ));
 
     for _i in 0..2 {
-        spawn_new_enemy(
+        spawn_enemy(
             &mut commands,
-            &asset_server,
-            &Transform::from_xyz(0., 0., 1.),
+            &assets,
+            &settings,
+            EnemyKind::Big,
+            spawn_position(&playfield, &Transform::from_xyz(0., 0., 1.)),
         );
     }
 }
 
-/// The sprite is animated by changing its translation depending on the time that has passed since
-/// the last frame.
-fn sprite_movement(time: Res<Time>, mut sprite_position: Query<(&Movment, &mut Transform)>) {
-    for (move_params, mut transform) in &mut sprite_position {
-        let delta = Transform::from_xyz(0., move_params.speed * time.delta_seconds(), 0.);
-        transform.rotate_local_z(-move_params.look_direction + move_params.heading);
-        *transform = transform
-            .mul_transform(delta)
-            .with_rotation(Quat::from_rotation_z(move_params.heading));
-        transform.rotate_local_z(move_params.look_direction - move_params.heading);
+/// Slows down and caps velocities, then moves and turns everything that has one.
+fn integrate_motion(
+    time: Res<GameTime>,
+    mut bodies: Query<(
+        &mut Transform,
+        &mut Velocity,
+        Option<&Drag>,
+        Option<&MaxSpeed>,
+        Option<&mut Facing>,
+        Option<&AngularVelocity>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (mut transform, mut velocity, drag, max_speed, facing, spin) in &mut bodies {
+        if let Some(Drag(drag)) = drag {
+            let speed = (velocity.0.length() - drag * dt).max(0.);
+            velocity.0 = velocity.0.clamp_length_max(speed);
+        }
+        if let Some(MaxSpeed(max)) = max_speed {
+            velocity.0 = velocity.0.clamp_length_max(*max);
+        }
+        transform.translation += (velocity.0 * dt).extend(0.);
+
+        if let Some(mut facing) = facing {
+            if let Some(AngularVelocity(spin)) = spin {
+                facing.0 += spin * dt;
+            }
+            transform.rotation = Quat::from_rotation_z(facing.0);
+        }
+    }
+}
+
+/// Stick travel ignored around the center.
+const THRUST_DEADZONE: f32 = 0.15;
+/// Thrust right past the deadzone, high enough to beat the ship drag.
+const MIN_THRUST: f32 = 0.5;
+
+/// Maps the thrust input onto the thrust applied, so that a partly tilted
+/// stick still gets the ship moving. Keys give 0 or 1 and are unchanged.
+fn thrust_response(input: f32) -> f32 {
+    if input <= THRUST_DEADZONE {
+        0.
+    } else {
+        MIN_THRUST + (1. - MIN_THRUST) * (input - THRUST_DEADZONE) / (1. - THRUST_DEADZONE)
     }
 }
 
 fn move_block(
-    time: Res<Time>,
-    keyboard_input: Res<Input<KeyCode>>,
-    asset_server: Res<AssetServer>,
+    time: Res<GameTime>,
+    actions: Res<Actions>,
+    assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    playfield: Res<Playfield>,
     mut commands: Commands,
-    mut query: Query<(&mut Movment, &Transform, &mut Player), With<Player>>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut query: Query<
+        (
+            &mut Velocity,
+            &mut AngularVelocity,
+            &Facing,
+            &Transform,
+            &Weapon,
+            &mut Emitter,
+            &mut Animation,
+            &mut Player,
+        ),
+        With<Player>,
+    >,
 ) {
-    if let Ok((mut block, trans, mut pl)) = query.get_single_mut() {
+    if let Ok((
+        mut velocity,
+        mut spin,
+        facing,
+        trans,
+        Weapon(weapon),
+        mut flame,
+        mut animation,
+        mut pl,
+    )) = query.get_single_mut()
+    {
         pl.0.tick(time.delta());
 
-        if keyboard_input.pressed(KeyCode::W) {
-            let delta_speed = 250. * time.delta_seconds();
-            let new_speed = (((delta_speed * block.look_direction.sin())
-                + (block.speed * block.heading.sin()))
-            .powi(2)
-                + ((delta_speed * block.look_direction.cos())
-                    + (block.speed * block.heading.cos()))
-                .powi(2))
-            .sqrt();
-            let new_heading = ((delta_speed * block.look_direction.sin())
-                + (block.speed * block.heading.sin()))
-            .atan2(
-                (delta_speed * block.look_direction.cos()) + (block.speed * block.heading.cos()),
-            );
-
-            block.speed = new_speed;
-            block.heading = new_heading;
-
-            if block.speed > 500. {
-                block.speed = 500.
-            }
+        // Thrust has to beat the drag that is always acting on the ship
+        let thrust = thrust_response(actions.value(Action::Thrust));
+        velocity.0 += direction(facing.0) * 375. * thrust * time.delta_seconds();
+        spin.0 = PI * (actions.value(Action::TurnLeft) - actions.value(Action::TurnRight));
+        flame.intensity = thrust;
+        animation.play(if flame.intensity > 0. {
+            Clip::Thrusting
         } else {
-            block.speed -= 125. * time.delta_seconds();
-            if block.speed < 0. {
-                block.speed = 0.;
-            }
-        }
-
-        if keyboard_input.pressed(KeyCode::A) {
-            block.look_direction += (180. * time.delta_seconds()).to_radians();
-        }
-
-        if keyboard_input.pressed(KeyCode::D) {
-            block.look_direction -= (180. * time.delta_seconds()).to_radians();
-        }
+            Clip::Idle
+        });
 
-        if keyboard_input.pressed(KeyCode::Space) {
+        if actions.pressed(Action::Fire) {
             if pl.0.finished() {
-                commands
-                    .spawn_bundle(SpriteBundle {
-                        texture: asset_server.load("bullet.png"),
-                        sprite: Sprite {
-                            color: Color::WHITE,
-                            custom_size: Some(Vec2::new(5.0, 17.0)),
-                            ..default()
-                        },
-                        transform: trans.mul_transform(Transform::from_translation(trans.up())),
-                        ..default()
-                    })
-                    .insert(Movment {
-                        speed: 700.,
-                        heading: block.look_direction,
-                        look_direction: block.look_direction,
-                    })
-                    .insert(Nowarp)
-                    .insert(Bullet);
+                let aim = direction(facing.0);
+                let mut bullet = spawn_bullet(
+                    &mut commands,
+                    &assets,
+                    Color::WHITE,
+                    trans
+                        .mul_transform(Transform::from_translation(trans.up()))
+                        .translation,
+                    facing.0,
+                    aim * weapon.speed + velocity.0 * weapon.inherit,
+                );
+                bullet.insert(Bullet);
+                if settings.bullet_wrap {
+                    bullet.insert(Range(settings.bullet_range.widths() * playfield.size.x));
+                } else {
+                    bullet.insert(Nowarp);
+                }
+                velocity.0 -= aim * weapon.recoil;
                 pl.0.reset();
+                sfx.send(PlaySfx(Sfx::Fire));
             }
         }
     }
 }
 
-fn warp_system(mut query: Query<&mut Transform, Without<Nowarp>>) {
+fn warp_system(
+    playfield: Res<Playfield>,
+    mut query: Query<&mut Transform, (Without<Nowarp>, Without<Ghost>)>,
+) {
+    let half = playfield.half();
     for mut b in &mut query {
-        if b.translation.x.abs() > W_WIDTH / 2. + 5. {
+        if b.translation.x.abs() > half.x + 5. {
             b.translation.x = -(b.translation.x - (5. * b.translation.x.signum()))
         }
-        if b.translation.y.abs() > W_HEIGHT / 2. + 5. {
+        if b.translation.y.abs() > half.y + 5. {
             b.translation.y = -(b.translation.y - (5. * b.translation.y.signum()))
         }
     }
 }
 
-fn cleanup(mut commands: Commands, query: Query<(Entity, &Transform), With<Nowarp>>) {
+/// Sprite components a [`Ghost`] can copy, plain or from a texture atlas.
+trait GhostSprite: Component + Clone {
+    fn size(&self) -> Option<Vec2>;
+}
+
+impl GhostSprite for Sprite {
+    fn size(&self) -> Option<Vec2> {
+        self.custom_size
+    }
+}
+
+impl GhostSprite for TextureAtlasSprite {
+    fn size(&self) -> Option<Vec2> {
+        self.custom_size
+    }
+}
+
+/// Keeps a [`Ghost`] copy on the far side of every screen edge a wrapping
+/// sprite currently overlaps, so it is drawn whole while crossing. Child
+/// sprites, like boss turrets, get ghosts under the ghost of their parent.
+/// Sprites are only copied again when the source changes.
+fn update_ghosts<S: GhostSprite, T: Component + Clone>(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    sources: Query<
+        (
+            Entity,
+            &Transform,
+            &S,
+            &T,
+            ChangeTrackers<S>,
+            ChangeTrackers<T>,
+            Option<&Children>,
+        ),
+        (With<Velocity>, Without<Nowarp>, Without<Ghost>),
+    >,
+    parts: Query<(&Transform, &S, &T, ChangeTrackers<S>, ChangeTrackers<T>), Without<Ghost>>,
+    mut ghosts: Query<(
+        Entity,
+        &Ghost,
+        Option<&Parent>,
+        &mut Transform,
+        &mut S,
+        &mut T,
+    )>,
+) {
+    let half = playfield.half();
+    // Ghost transform, sprite, texture, whether the sprite changed, and the
+    // source root for child sprites
+    let mut wanted = HashMap::new();
+    for (e, t, sprite, texture, sprite_changes, texture_changes, children) in &sources {
+        let children: Vec<_> = children
+            .into_iter()
+            .flat_map(|c| c.iter())
+            .filter_map(|c| parts.get(*c).ok().map(|part| (*c, part)))
+            .collect();
+        // Generous bound, sprites may be anchored away from their center
+        let size = |sprite: &S| sprite.size().map_or(0., |s| s.length());
+        let reach = children
+            .iter()
+            .map(|(_, (ct, cs, ..))| ct.translation.truncate().length() + size(cs))
+            .fold(size(sprite), f32::max);
+        let p = t.translation.truncate();
+        let side = |v: f32, half: f32| {
+            if v > half - reach {
+                -1
+            } else if v < -half + reach {
+                1
+            } else {
+                0
+            }
+        };
+        let (sx, sy) = (side(p.x, half.x), side(p.y, half.y));
+        for s in [IVec2::new(sx, 0), IVec2::new(0, sy), IVec2::new(sx, sy)] {
+            if s == IVec2::ZERO {
+                continue;
+            }
+            let changed = sprite_changes.is_changed() || texture_changes.is_changed();
+            let root = ghost_transform(t, s, playfield.size);
+            wanted.insert((e, s), (root, sprite, texture, changed, None));
+            for (c, (ct, cs, ctex, cs_changes, ctex_changes)) in &children {
+                let changed = cs_changes.is_changed() || ctex_changes.is_changed();
+                wanted.insert((*c, s), (**ct, *cs, *ctex, changed, Some(e)));
+            }
+        }
+    }
+
+    // Ghosts of sources, by source and side
+    let mut roots = HashMap::new();
+    let mut stale_parts = Vec::new();
+    for (ge, ghost, parent, mut gt, mut gs, mut gtex) in &mut ghosts {
+        match wanted.remove(&(ghost.source, ghost.side)) {
+            Some((t, sprite, texture, changed, root)) => {
+                *gt = t;
+                if changed {
+                    *gs = sprite.clone();
+                    *gtex = texture.clone();
+                }
+                if root.is_none() {
+                    roots.insert((ghost.source, ghost.side), ge);
+                }
+            }
+            None => match parent {
+                Some(parent) => stale_parts.push((parent.get(), ge)),
+                None => commands.entity(ge).despawn_recursive(),
+            },
+        }
+    }
+    // Parts of ghosts going away are despawned along with them
+    for (parent, ge) in stale_parts {
+        if roots.values().any(|root| *root == parent) {
+            commands.entity(ge).despawn();
+        }
+    }
+
+    let mut new_parts = Vec::new();
+    for ((source, side), (t, sprite, texture, _, root)) in wanted {
+        match root {
+            Some(root) => new_parts.push((root, source, side, t, sprite, texture)),
+            None => {
+                let ghost = commands
+                    .spawn_bundle(ghost_bundle(sprite, texture, t))
+                    .insert(Ghost { source, side })
+                    .id();
+                roots.insert((source, side), ghost);
+            }
+        }
+    }
+    for (root, source, side, t, sprite, texture) in new_parts {
+        if let Some(ghost) = roots.get(&(root, side)) {
+            let part = commands
+                .spawn_bundle(ghost_bundle(sprite, texture, t))
+                .insert(Ghost { source, side })
+                .id();
+            commands.entity(*ghost).add_child(part);
+        }
+    }
+}
+
+fn ghost_bundle<S: GhostSprite, T: Component + Clone>(
+    sprite: &S,
+    texture: &T,
+    transform: Transform,
+) -> (
+    S,
+    T,
+    Transform,
+    GlobalTransform,
+    Visibility,
+    ComputedVisibility,
+) {
+    (
+        sprite.clone(),
+        texture.clone(),
+        transform,
+        GlobalTransform::default(),
+        Visibility::default(),
+        ComputedVisibility::default(),
+    )
+}
+
+fn ghost_transform(source: &Transform, side: IVec2, size: Vec2) -> Transform {
+    let mut t = *source;
+    t.translation += (side.as_vec2() * size).extend(0.);
+    t
+}
+
+fn cleanup(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    query: Query<(Entity, &Transform), With<Nowarp>>,
+) {
+    let half = playfield.half();
     for (e, b) in &query {
         match (b.translation.x.abs(), b.translation.y.abs()) {
-            (a, _) if a > W_WIDTH / 2. => {
+            (a, _) if a > half.x => {
                 commands.entity(e).despawn();
             }
-            (_, b) if b > W_HEIGHT / 2. => {
+            (_, b) if b > half.y => {
                 commands.entity(e).despawn();
             }
             _ => (),
@@ -591,162 +2136,779 @@ fn cleanup(mut commands: Commands, query: Query<(Entity, &Transform), With<Nowar
     }
 }
 
+fn bullet_range(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    mut query: Query<(Entity, &Velocity, &mut Range), With<Bullet>>,
+) {
+    for (e, v, mut range) in &mut query {
+        range.0 -= v.0.length() * time.delta_seconds();
+        if range.0 <= 0. {
+            commands.entity(e).despawn();
+        }
+    }
+}
+
 fn enemy_spawner(
     mut commands: Commands,
-    time: Res<Time>,
-    asset_server: Res<AssetServer>,
+    time: Res<GameTime>,
+    assets: Res<GameAssets>,
+    playfield: Res<Playfield>,
+    settings: Res<Settings>,
     query: Query<&Transform, With<Player>>,
     mut player: Query<&mut Player, With<Player>>,
+    bosses: Query<(), With<Boss>>,
 ) {
     if let Ok(mut pl) = player.get_single_mut() {
         let spawn_timer = &mut pl.1;
         spawn_timer.tick(time.delta());
 
-        if spawn_timer.finished() {
-            spawn_new_enemy(&mut commands, &asset_server, query.single());
+        // Regular waves hold off while a boss is around
+        if spawn_timer.finished() && bosses.is_empty() {
+            spawn_enemy(
+                &mut commands,
+                &assets,
+                &settings,
+                EnemyKind::random(),
+                spawn_position(&playfield, query.single()),
+            );
             spawn_timer.reset();
         }
     }
 }
 
-fn check_colision(
-    t1: &Transform,
-    w1: f32,
-    h1: f32,
-    r1: f32,
-    t2: &Transform,
-    w2: f32,
-    h2: f32,
-    r2: f32,
-) -> bool {
-    let mut t1c = t1.clone();
-    let mut t2c = t2.clone();
+/// Heading (as used by [`direction`]) pointing from `from` toward `to`.
+fn heading_to(from: Vec3, to: Vec3) -> f32 {
+    angle_of((to - from).truncate())
+}
 
-    t2c.translation -= t1.translation;
+/// Rotates `heading` toward `target` by at most `max_turn` radians.
+fn turn_toward(heading: f32, target: f32, max_turn: f32) -> f32 {
+    let diff = (target - heading + PI).rem_euclid(2. * PI) - PI;
+    heading + diff.clamp(-max_turn, max_turn)
+}
 
-    for (x, y) in [(0.5, 0.5), (-0.5, 0.5), (0.5, -0.5), (-0.5, -0.5)] {
-        let p = Vec2::from_angle(r2 - r1).rotate(Vec2 {
-            x: w2 * x + t2c.translation.x,
-            y: h2 * y + t2c.translation.y,
-        });
-        if p.abs()
-            .cmple(Vec2 {
-                x: w1 / 2.,
-                y: h1 / 2.,
-            })
-            .all()
-        {
-            return true;
+fn spawn_enemy_bullet(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    palette: Palette,
+    owner: Entity,
+    position: Vec3,
+    heading: f32,
+) {
+    let color = match palette {
+        Palette::Standard => Color::RED,
+        Palette::Colorblind => Color::rgb(0.9, 0.62, 0.),
+    };
+    spawn_bullet(
+        commands,
+        assets,
+        color,
+        position,
+        heading,
+        direction(heading) * 400.,
+    )
+    .insert(Nowarp)
+    .insert(EnemyBullet { owner });
+}
+
+fn enemy_behavior(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    playfield: Res<Playfield>,
+    player: Query<&Transform, With<Player>>,
+    mut enemies: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Velocity,
+            &mut Facing,
+            &mut Behavior,
+        ),
+        With<Enemy>,
+    >,
+) {
+    let player_pos = match player.get_single() {
+        Ok(pt) => pt.translation.truncate(),
+        Err(_) => return,
+    };
+    let dt = time.delta_seconds();
+
+    for (ee, et, mut v, mut f, mut b) in &mut enemies {
+        b.elapsed += dt;
+        // Chase the player across the wrapping edges when that is shorter
+        let pp = playfield
+            .nearest(et.translation.truncate(), player_pos)
+            .extend(et.translation.z);
+        let to_player = heading_to(et.translation, pp);
+        let distance = et.translation.distance(pp);
+
+        match b.kind {
+            BehaviorKind::Drift => (),
+            BehaviorKind::Seek { turn_rate } => {
+                v.steer(to_player, turn_rate * dt);
+                f.0 = v.heading();
+            }
+            BehaviorKind::Shoot { range, reload } => {
+                let target = if distance > range {
+                    to_player
+                } else {
+                    to_player + PI / 2.
+                };
+                v.steer(target, PI * dt);
+
+                b.cooldown -= dt;
+                if b.cooldown <= 0. {
+                    b.cooldown = reload;
+                    spawn_enemy_bullet(
+                        &mut commands,
+                        &assets,
+                        settings.palette,
+                        ee,
+                        et.translation,
+                        to_player,
+                    );
+                }
+            }
+            BehaviorKind::Orbit { radius } => {
+                // Fly tangentially, leaning in or out to hold the radius
+                let correction = ((distance - radius) / radius).clamp(-1., 1.) * PI / 4.;
+                v.steer(to_player + PI / 2. - correction, 2. * PI * dt);
+                f.0 = v.heading();
+            }
+            BehaviorKind::ZigZag {
+                amplitude,
+                frequency,
+            } => {
+                let heading = b.base_heading + amplitude * (b.elapsed * frequency * 2. * PI).sin();
+                v.0 = direction(heading) * v.0.length();
+                f.0 = heading;
+            }
         }
     }
+}
 
-    t1c.translation -= t2.translation;
+fn spawn_boss(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    playfield: &Playfield,
+    player_pos: &Transform,
+) {
+    let mut transform = spawn_position(playfield, player_pos);
+    transform.rotation = Quat::IDENTITY;
+    let core_size = Vec2::new(120., 170.);
+    let turret_size = Vec2::new(50., 70.);
+    let turret_offsets = [Vec2::new(-110., 20.), Vec2::new(110., 20.)];
 
-    for (x, y) in [(0.5, 0.5), (-0.5, 0.5), (0.5, -0.5), (-0.5, -0.5)] {
-        let p = Vec2::from_angle(r1 - r2).rotate(Vec2 {
-            x: w1 * x + t1c.translation.x,
-            y: h1 * y + t1c.translation.y,
-        });
-        if p.abs()
-            .cmple(Vec2 {
-                x: w2 / 2.,
-                y: h2 / 2.,
+    let mut turrets = Vec::new();
+    let root = commands
+        .spawn_bundle(SpriteBundle {
+            texture: assets.boss_core.clone(),
+            sprite: Sprite {
+                custom_size: Some(core_size),
+                ..default()
+            },
+            transform,
+            ..default()
+        })
+        .insert(Velocity(
+            direction(heading_to(transform.translation, player_pos.translation)) * 60.,
+        ))
+        .insert(Facing(0.))
+        .with_children(|parent| {
+            for offset in turret_offsets {
+                let turret = parent
+                    .spawn_bundle(SpriteBundle {
+                        texture: assets.boss_turret.clone(),
+                        sprite: Sprite {
+                            custom_size: Some(turret_size),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(offset.extend(-0.1)),
+                        ..default()
+                    })
+                    .id();
+                turrets.push((offset, turret));
+            }
+        })
+        .id();
+
+    let mut parts = vec![BossPart {
+        offset: Vec2::ZERO,
+        shape: Shape::Circle(core_size.x * 0.5),
+        health: 20,
+        max_health: 20,
+        core: true,
+        sprite: root,
+    }];
+    parts.extend(turrets.into_iter().map(|(offset, sprite)| BossPart {
+        offset,
+        shape: Shape::Rect(turret_size * 0.9),
+        health: 6,
+        max_health: 6,
+        core: false,
+        sprite,
+    }));
+    commands.entity(root).insert(Boss {
+        parts,
+        phase: BossPhase::Escort,
+        cooldown: 2.,
+        elapsed: 0.,
+    });
+
+    // Full width row keeping the bar centered whatever the window size
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Px(16.)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(20.0),
+                    ..default()
+                },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(BossHud)
+        .with_children(|row| {
+            row.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(400.), Val::Percent(100.)),
+                    ..default()
+                },
+                color: Color::DARK_GRAY.into(),
+                ..default()
             })
-            .all()
-        {
-            return true;
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                            ..default()
+                        },
+                        color: Color::CRIMSON.into(),
+                        ..default()
+                    })
+                    .insert(BossHealthBar);
+            });
+        });
+}
+
+fn boss_spawner(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    playfield: Res<Playfield>,
+    score: Res<Score>,
+    next_boss: Res<NextBoss>,
+    player: Query<&Transform, With<Player>>,
+    bosses: Query<(), With<Boss>>,
+) {
+    if score.0 < next_boss.0 || !bosses.is_empty() {
+        return;
+    }
+    if let Ok(pt) = player.get_single() {
+        spawn_boss(&mut commands, &assets, &playfield, pt);
+    }
+}
+
+fn boss_behavior(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    playfield: Res<Playfield>,
+    player: Query<&Transform, With<Player>>,
+    mut bosses: Query<(Entity, &Transform, &mut Velocity, &mut Boss)>,
+) {
+    let player_pos = match player.get_single() {
+        Ok(pt) => pt.translation.truncate(),
+        Err(_) => return,
+    };
+    let dt = time.delta_seconds();
+    let nearest_player = |from: Vec3| {
+        playfield
+            .nearest(from.truncate(), player_pos)
+            .extend(from.z)
+    };
+
+    for (be, bt, mut v, mut boss) in &mut bosses {
+        boss.elapsed += dt;
+        boss.cooldown -= dt;
+        let to_player = heading_to(bt.translation, nearest_player(bt.translation));
+
+        match boss.phase {
+            BossPhase::Escort => {
+                v.0 = direction(turn_toward(v.heading(), to_player, 0.3 * dt)) * 60.;
+                if boss.cooldown <= 0. {
+                    boss.cooldown = 1.2;
+                    for part in boss.parts.iter().filter(|p| !p.core && p.health > 0) {
+                        let from = bt
+                            .mul_transform(Transform::from_translation(part.offset.extend(0.)))
+                            .translation;
+                        spawn_enemy_bullet(
+                            &mut commands,
+                            &assets,
+                            settings.palette,
+                            be,
+                            from,
+                            heading_to(from, nearest_player(from)),
+                        );
+                    }
+                }
+            }
+            BossPhase::Exposed | BossPhase::Enraged => {
+                let (speed, reload, count) = if boss.phase == BossPhase::Exposed {
+                    (90., 2., 8)
+                } else {
+                    (150., 1.2, 12)
+                };
+                v.0 = direction(turn_toward(v.heading(), to_player, 0.6 * dt)) * speed;
+                if boss.cooldown <= 0. {
+                    boss.cooldown = reload;
+                    for i in 0..count {
+                        let heading = boss.elapsed + i as f32 * 2. * PI / count as f32;
+                        spawn_enemy_bullet(
+                            &mut commands,
+                            &assets,
+                            settings.palette,
+                            be,
+                            bt.translation,
+                            heading,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn boss_hits(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    playfield: Res<Playfield>,
+    bullets: Query<(Entity, &Transform, &Velocity, &Facing), With<Bullet>>,
+    mut spent: ResMut<SpentBullets>,
+    mut bosses: Query<(Entity, &Transform, &Velocity, &Facing, &mut Boss)>,
+    mut sprites: Query<&mut Sprite>,
+    hud: Query<Entity, With<BossHud>>,
+    mut text: Query<&mut Text, With<ScoreText>>,
+    mut score: ResMut<Score>,
+    mut next_boss: ResMut<NextBoss>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut bursts: EventWriter<Burst>,
+    mut juice: EventWriter<Juice>,
+) {
+    for (bullet, bt, bv, bf) in &bullets {
+        if spent.0.contains(&bullet) {
+            continue;
+        }
+        let bullet_shape = BULLET_SHAPE.at(bt.translation.truncate(), bf.0);
+        for (be, t, v, f, mut boss) in &mut bosses {
+            let motion = (bv.0 - v.0) * time.delta_seconds();
+            let center = playfield.nearest(bullet_shape.position, t.translation.truncate());
+            if boss.is_dead()
+                || center.distance(bullet_shape.position) > BOSS_REACH + motion.length()
+            {
+                continue;
+            }
+            let hit = match (0..boss.parts.len()).find(|&i| {
+                boss.parts[i].health > 0
+                    && collide_swept(&bullet_shape, motion, &boss.parts[i].placed(center, f.0))
+                        .is_some()
+            }) {
+                Some(i) => i,
+                None => continue,
+            };
+            commands.entity(bullet).despawn();
+            spent.0.insert(bullet);
+            bursts.send(Burst {
+                heading: bf.0 + PI,
+                ..Burst::new(&IMPACT, bullet_shape.position)
+            });
+
+            // The core shrugs off hits while turrets are standing
+            if boss.parts[hit].core && boss.phase == BossPhase::Escort {
+                break;
+            }
+
+            let part = &mut boss.parts[hit];
+            part.health -= 1;
+            let (core, health, max_health, sprite) =
+                (part.core, part.health, part.max_health, part.sprite);
+            if let Ok(mut s) = sprites.get_mut(sprite) {
+                let ratio = health as f32 / max_health as f32;
+                s.color = Color::rgb(1., ratio, ratio);
+            }
+
+            if core && health == 0 {
+                sfx.send(PlaySfx(Sfx::Explode));
+                juice.send(Juice::BOSS_KILL);
+                bursts.send(Burst {
+                    velocity: v.0,
+                    ..Burst::new(&EXPLOSION, t.translation.truncate())
+                });
+                commands.entity(be).despawn_recursive();
+                for h in &hud {
+                    commands.entity(h).despawn_recursive();
+                }
+                score.add(BOSS_SCORE);
+                // Count from the kill so the boss's own score cannot summon the next one
+                next_boss.0 = score.0 + BOSS_EVERY;
+                text.get_single_mut().unwrap().sections[0].value = format!("Score: {}", score.0);
+            } else if core && health * 2 <= max_health {
+                boss.phase = BossPhase::Enraged;
+            } else if health == 0 {
+                sfx.send(PlaySfx(Sfx::Split));
+                juice.send(Juice::BIG_KILL);
+                bursts.send(Burst {
+                    velocity: v.0,
+                    ..Burst::new(
+                        &DEBRIS,
+                        boss.parts[hit]
+                            .placed(t.translation.truncate(), f.0)
+                            .position,
+                    )
+                });
+                commands.entity(sprite).despawn_recursive();
+                if boss.parts.iter().all(|p| p.core || p.health == 0) {
+                    boss.phase = BossPhase::Exposed;
+                }
+            }
+            break;
+        }
+    }
+}
+
+fn boss_hud(bosses: Query<&Boss>, mut bars: Query<&mut Style, With<BossHealthBar>>) {
+    if let Ok(boss) = bosses.get_single() {
+        for mut bar in &mut bars {
+            bar.size.width = Val::Percent(boss.health_ratio() * 100.);
+        }
+    }
+}
+
+/// Radius an enemy is filed under in the [`EnemyGrid`]. It leaves room for
+/// the distance covered this frame, bullets are swept against it.
+fn grid_radius(hitbox: &Shape, velocity: Vec2, time: &GameTime) -> f32 {
+    hitbox.radius() + velocity.length() * time.delta_seconds()
+}
+
+fn update_enemy_grid(
+    time: Res<GameTime>,
+    mut grid: ResMut<EnemyGrid>,
+    enemies: Query<(Entity, &Transform, &Velocity, &Enemy)>,
+) {
+    grid.0.clear();
+    for (e, t, v, enemy) in &enemies {
+        let radius = grid_radius(&enemy.kind.def().hitbox, v.0, &time);
+        grid.0.insert(e, t.translation.truncate(), radius);
+    }
+}
+
+/// Resolves overlapping enemies as elastic collisions when
+/// [`Settings::enemy_physics`] is on, pushing them apart and exchanging
+/// momentum along the contact normal. The grid is filed again afterwards so
+/// the hit tests see the enemies where they ended up.
+fn enemy_bounces(
+    time: Res<GameTime>,
+    settings: Res<Settings>,
+    playfield: Res<Playfield>,
+    mut grid: ResMut<EnemyGrid>,
+    mut enemies: Query<(Entity, &mut Transform, &mut Velocity, &Facing, &Enemy)>,
+) {
+    if !settings.enemy_physics {
+        return;
+    }
+
+    struct Body {
+        entity: Entity,
+        position: Vec2,
+        velocity: Vec2,
+        rotation: f32,
+        mass: f32,
+        hitbox: &'static Shape,
+        touched: bool,
+    }
+
+    let mut bodies: Vec<Body> = enemies
+        .iter()
+        .map(|(e, t, v, f, enemy)| Body {
+            entity: e,
+            position: t.translation.truncate(),
+            velocity: v.0,
+            rotation: f.0,
+            mass: enemy.kind.def().mass(),
+            hitbox: &enemy.kind.def().hitbox,
+            touched: false,
+        })
+        .collect();
+    let index: HashMap<Entity, usize> = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (b.entity, i))
+        .collect();
+
+    for i in 0..bodies.len() {
+        for other in grid.0.query(bodies[i].position, bodies[i].hitbox.radius()) {
+            let j = match index.get(&other) {
+                Some(&j) if j > i => j,
+                _ => continue,
+            };
+            let (a, b) = (&bodies[i], &bodies[j]);
+            let pb = playfield.nearest(a.position, b.position);
+            let contact = match collide(
+                &a.hitbox.at(a.position, a.rotation),
+                &b.hitbox.at(pb, b.rotation),
+            ) {
+                Some(contact) => contact,
+                None => continue,
+            };
+
+            let n = contact.normal;
+            let (inv_a, inv_b) = (1. / a.mass, 1. / b.mass);
+            let share_a = inv_a / (inv_a + inv_b);
+            let approach = (b.velocity - a.velocity).dot(n);
+            // Only separating pairs are left alone, they are already bouncing apart
+            let impulse = if approach < 0. {
+                -2. * approach / (inv_a + inv_b)
+            } else {
+                0.
+            };
+
+            let a = &mut bodies[i];
+            a.position -= n * contact.depth * share_a;
+            a.velocity -= n * impulse * inv_a;
+            a.touched = true;
+            let b = &mut bodies[j];
+            b.position += n * contact.depth * (1. - share_a);
+            b.velocity += n * impulse * inv_b;
+            b.touched = true;
         }
     }
 
-    false
+    if !bodies.iter().any(|b| b.touched) {
+        return;
+    }
+    grid.0.clear();
+    for body in &bodies {
+        let radius = grid_radius(body.hitbox, body.velocity, &time);
+        grid.0.insert(body.entity, body.position, radius);
+        if !body.touched {
+            continue;
+        }
+        if let Ok((_, mut t, mut v, _, _)) = enemies.get_mut(body.entity) {
+            t.translation = body.position.extend(t.translation.z);
+            v.0 = body.velocity;
+        }
+    }
 }
 
 fn bullet_hits(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    bullets: Query<(Entity, &Transform, &Movment), With<Bullet>>,
-    enemies: Query<(Entity, &Transform, &Movment, &Enemy), With<Enemy>>,
-    mut text: Query<&mut Text>,
+    time: Res<GameTime>,
+    assets: Res<GameAssets>,
+    playfield: Res<Playfield>,
+    settings: Res<Settings>,
+    grid: Res<EnemyGrid>,
+    bullets: Query<
+        (Entity, &Transform, &Velocity, &Facing, Option<&EnemyBullet>),
+        Or<(With<Bullet>, With<EnemyBullet>)>,
+    >,
+    mut spent: ResMut<SpentBullets>,
+    mut enemies: Query<(Entity, &Transform, &Velocity, &Facing, &mut Enemy)>,
+    mut text: Query<&mut Text, With<ScoreText>>,
     mut score: ResMut<Score>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut bursts: EventWriter<Burst>,
+    mut juice: EventWriter<Juice>,
 ) {
-    bullets.for_each(|(be, bt, bm)| {
-        enemies.for_each(|(ee, et, em, e)| {
-            if et.translation.distance(bt.translation) < (17. + 50.) / 2. {
-                if check_colision(
-                    bt,
-                    5.,
-                    17.,
-                    bm.look_direction,
-                    et,
-                    35.,
-                    50.,
-                    em.look_direction,
-                ) {
-                    commands.entity(be).despawn();
-                    let lifes = e.0;
-                    commands.entity(ee).despawn();
-                    score.add((4 - lifes) as u64 * 50);
+    spent.0.clear();
+    for (be, bt, bv, bf, enemy_bullet) in &bullets {
+        if enemy_bullet.is_some() && !settings.enemy_friendly_fire {
+            continue;
+        }
+        let reach = BULLET_SHAPE.radius() + bv.0.length() * time.delta_seconds();
+        for ee in grid.0.query(bt.translation.truncate(), reach) {
+            let (_, et, ev, ef, mut e) = match enemies.get_mut(ee) {
+                Ok(enemy) => enemy,
+                Err(_) => continue,
+            };
+            // Already destroyed by another bullet this frame
+            if e.health == 0 {
+                continue;
+            }
+            if matches!(enemy_bullet, Some(EnemyBullet { owner }) if *owner == ee) {
+                continue;
+            }
+            let def = e.kind.def();
+            // Sweep the bullet over the path it travelled relative to the enemy
+            // this frame, so low frame rates cannot make it skip the enemy
+            let motion = (bv.0 - ev.0) * time.delta_seconds();
+            let bp = bt.translation.truncate();
+            let ep = playfield.nearest(bp, et.translation.truncate());
+            if ep.distance(bp) < BULLET_SHAPE.radius() + def.hitbox.radius() + motion.length()
+                && collide_swept(&BULLET_SHAPE.at(bp, bf.0), motion, &def.hitbox.at(ep, ef.0))
+                    .is_some()
+            {
+                commands.entity(be).despawn();
+                spent.0.insert(be);
+                bursts.send(Burst {
+                    heading: bf.0 + PI,
+                    ..Burst::new(&IMPACT, bp)
+                });
+                e.health -= 1;
+                if e.health > 0 {
+                    break;
+                }
+
+                commands.entity(ee).despawn();
+                bursts.send(Burst {
+                    velocity: ev.0,
+                    color: Some(def.color(settings.palette)),
+                    ..Burst::new(&DEBRIS, et.translation.truncate())
+                });
+                if def.split.is_some() {
+                    sfx.send(PlaySfx(Sfx::Split));
+                    juice.send(Juice::BIG_KILL);
+                } else {
+                    sfx.send(PlaySfx(Sfx::Explode));
+                    juice.send(Juice::KILL);
+                }
+                // Only the player scores, enemies shooting each other is a free bonus
+                if enemy_bullet.is_none() {
+                    score.add(def.score);
                     text.get_single_mut().unwrap().sections[0].value =
                         format!("Score: {}", score.0);
+                }
 
-                    if lifes > 0 {
-                        for _i in 0..2 {
-                            // let mut rng = rand::rngs::OsRng;
-                            let mut movement = Movment::default();
-                            movement.fill_rand();
-                            // movement.try_fill(&mut rng).unwrap();
-                            commands
-                                .spawn_bundle(SpriteBundle {
-                                    texture: asset_server.load(Enemy::get_asset_path(lifes - 1)),
-                                    sprite: Sprite {
-                                        color: Color::GOLD,
-                                        custom_size: Some(Vec2::new(35.0, 50.0)),
-                                        ..default()
-                                    },
-                                    transform: et.clone(),
-                                    ..default()
-                                })
-                                .insert(movement)
-                                .insert(Enemy(lifes - 1));
-                        }
+                if let Some((count, kind)) = def.split {
+                    for _i in 0..count {
+                        spawn_enemy(&mut commands, &assets, &settings, kind, *et);
                     }
                 }
+                break;
             }
-        })
-    })
+        }
+    }
+}
+
+/// Shows the damaged frames of enemies that took a hit.
+fn enemy_clips(mut enemies: Query<(&Enemy, &mut Animation), Changed<Enemy>>) {
+    for (enemy, mut animation) in &mut enemies {
+        if enemy.health < enemy.kind.def().health {
+            animation.play(Clip::Damaged);
+        }
+    }
 }
 
 fn player_hits(
-    player: Query<(&Transform, &Movment), With<Player>>,
-    enemies: Query<(&Transform, &Movment), With<Enemy>>,
-    mut state: ResMut<State<AppState>>,
+    mut commands: Commands,
+    time: Res<GameTime>,
+    playfield: Res<Playfield>,
+    grid: Res<EnemyGrid>,
+    mut player: Query<(Entity, &Transform, &Velocity, &Facing, &mut Animation), With<Player>>,
+    enemies: Query<(&Transform, &Facing, &Enemy)>,
+    bullets: Query<(Entity, &Transform, &Velocity, &Facing), With<EnemyBullet>>,
+    spent: Res<SpentBullets>,
+    bosses: Query<(&Transform, &Facing, &Boss)>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut bursts: EventWriter<Burst>,
+    mut juice: EventWriter<Juice>,
 ) {
-    if let Ok((pt, pm)) = player.get_single() {
-        for (et, em) in enemies.into_iter() {
-            let mut p_centr = pt.clone();
-            p_centr = p_centr
-                .mul_transform(Transform::from_xyz(0., -75. / 2., 0.))
-                .with_rotation(Quat::from_rotation_z(pm.look_direction));
-            if et.translation.distance(p_centr.translation) < (75. + 50.) / 2. {
-                if check_colision(
-                    &p_centr,
-                    42.,
-                    75.,
-                    pm.look_direction,
-                    et,
-                    35.,
-                    50.,
-                    em.look_direction,
-                ) {
-                    if state.set(AppState::GameOver).is_ok() {
-                        return;
-                    }
-                }
+    if let Ok((pe, pt, pv, pf, mut animation)) = player.get_single_mut() {
+        let mut p_centr = *pt;
+        p_centr = p_centr
+            .mul_transform(Transform::from_xyz(0., -75. / 2., 0.))
+            .with_rotation(Quat::from_rotation_z(pf.0));
+
+        let pp = p_centr.translation.truncate();
+        let player_shape = PLAYER_SHAPE.at(pp, pf.0);
+
+        let enemy_shapes = grid
+            .0
+            .query(pp, PLAYER_SHAPE.radius())
+            .filter_map(|e| enemies.get(e).ok())
+            .map(|(et, ef, e)| {
+                e.kind
+                    .def()
+                    .hitbox
+                    .at(playfield.nearest(pp, et.translation.truncate()), ef.0)
+            });
+        let boss_shapes = bosses.iter().flat_map(|(bt, bf, boss)| {
+            let center = playfield.nearest(pp, bt.translation.truncate());
+            boss.parts
+                .iter()
+                .filter(|p| p.health > 0)
+                .map(move |p| p.placed(center, bf.0))
+        });
+
+        let rammed = enemy_shapes.chain(boss_shapes).any(|other| {
+            other.position.distance(pp) < PLAYER_SHAPE.radius() + other.shape.radius()
+                && collide(&player_shape, &other).is_some()
+        });
+        let shot = bullets.iter().any(|(be, bt, bv, bf)| {
+            if spent.0.contains(&be) {
+                return false;
             }
+            let motion = (bv.0 - pv.0) * time.delta_seconds();
+            let bp = playfield.nearest(pp, bt.translation.truncate());
+            bp.distance(pp) < PLAYER_SHAPE.radius() + BULLET_SHAPE.radius() + motion.length()
+                && collide_swept(&BULLET_SHAPE.at(bp, bf.0), motion, &player_shape).is_some()
+        });
+
+        if rammed || shot {
+            // The wreck drifts on while its dying frames play
+            animation.play(Clip::Dying);
+            commands
+                .entity(pe)
+                .remove::<Player>()
+                .remove::<Emitter>()
+                .insert(DespawnAfterClip);
+            sfx.send(PlaySfx(Sfx::PlayerDeath));
+            juice.send(Juice::PLAYER_DEATH);
+            bursts.send(Burst {
+                velocity: pv.0,
+                ..Burst::new(&EXPLOSION, pp)
+            });
+            commands.insert_resource(DeathSequence(Timer::from_seconds(DEATH_DURATION, false)));
         }
     }
 }
 
+/// Moves on to the game over screen once the player explosion has played out.
+fn death_sequence(
+    time: Res<Time>,
+    death: Option<ResMut<DeathSequence>>,
+    mut game_time: ResMut<GameTime>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let mut death = match death {
+        Some(death) => death,
+        None => return,
+    };
+    let t = death.0.tick(time.delta()).percent();
+
+    // Slow right after the blast, then easing back to full speed
+    game_time.scale = 0.2 + 0.8 * ((t - 0.4) / 0.6).clamp(0., 1.);
+
+    if death.0.finished() {
+        let _ = state.set(AppState::GameOver);
+    }
+}
+
+/// Puts time back after a death sequence, also when it was cut short from
+/// the pause menu.
+fn end_death_sequence(mut commands: Commands, mut game_time: ResMut<GameTime>) {
+    commands.remove_resource::<DeathSequence>();
+    game_time.scale = 1.;
+    game_time.frozen = 0.;
+}
+
 #[wasm_bindgen(module = "/scores.js")]
 extern "C" {
     fn get_scores() -> String;
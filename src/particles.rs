@@ -0,0 +1,280 @@
+//! Short lived sprites for debris, engine flames, bullet impacts and
+//! explosions.
+//!
+//! Particles are taken from a pool of hidden sprites spawned with the game
+//! field and handed back when they fade out, so effects never spawn or
+//! despawn entities. Bursts finding the pool empty are cut short.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::{direction, get_randu64, Facing, GameTime, Velocity};
+
+const POOL_SIZE: usize = 512;
+/// Above enemies and the ship.
+const PARTICLE_Z: f32 = 2.;
+
+/// Look and motion of the particles an effect throws out.
+pub struct EffectDef {
+    /// Particles per burst, or per second for an [`Emitter`].
+    count: u32,
+    speed: (f32, f32),
+    /// Angle around the heading that particles are thrown within.
+    spread: f32,
+    lifetime: (f32, f32),
+    /// Size at birth and at death.
+    size: (f32, f32),
+    /// Color at birth and at death. A burst may replace the first one.
+    colors: (Color, Color),
+    /// Fraction of the speed lost per second.
+    drag: f32,
+}
+
+/// Pieces of a destroyed enemy, tinted like it.
+pub static DEBRIS: EffectDef = EffectDef {
+    count: 18,
+    speed: (60., 220.),
+    spread: 2. * PI,
+    lifetime: (0.4, 0.9),
+    size: (6., 2.),
+    colors: (Color::WHITE, Color::rgba(0.3, 0.3, 0.3, 0.)),
+    drag: 1.5,
+};
+
+/// Sparks bouncing back from a bullet hit.
+pub static IMPACT: EffectDef = EffectDef {
+    count: 6,
+    speed: (80., 200.),
+    spread: PI / 2.,
+    lifetime: (0.1, 0.25),
+    size: (3., 1.),
+    colors: (Color::rgb(1., 0.95, 0.6), Color::rgba(1., 0.5, 0., 0.)),
+    drag: 4.,
+};
+
+pub static FLAME: EffectDef = EffectDef {
+    count: 90,
+    speed: (120., 200.),
+    spread: PI / 6.,
+    lifetime: (0.15, 0.3),
+    size: (7., 2.),
+    colors: (Color::rgb(1., 0.85, 0.3), Color::rgba(1., 0.2, 0., 0.)),
+    drag: 2.,
+};
+
+/// Blast of the player ship or a boss.
+pub static EXPLOSION: EffectDef = EffectDef {
+    count: 120,
+    speed: (40., 380.),
+    spread: 2. * PI,
+    lifetime: (0.6, 1.6),
+    size: (10., 3.),
+    colors: (Color::rgb(1., 0.9, 0.5), Color::rgba(0.8, 0.1, 0., 0.)),
+    drag: 1.8,
+};
+
+/// Asks for a one-off effect.
+pub struct Burst {
+    pub effect: &'static EffectDef,
+    pub position: Vec2,
+    /// Direction particles are thrown toward, within the effect spread.
+    pub heading: f32,
+    /// Added to every particle, so debris keeps drifting with what broke.
+    pub velocity: Vec2,
+    pub color: Option<Color>,
+}
+
+impl Burst {
+    pub fn new(effect: &'static EffectDef, position: Vec2) -> Self {
+        Burst {
+            effect,
+            position,
+            heading: 0.,
+            velocity: Vec2::ZERO,
+            color: None,
+        }
+    }
+}
+
+/// Continuous effect attached to an entity with a [`Facing`].
+#[derive(Component)]
+pub struct Emitter {
+    pub effect: &'static EffectDef,
+    /// Emission point relative to the entity, turned with it.
+    pub offset: Vec2,
+    /// Emission heading relative to the entity facing.
+    pub heading: f32,
+    /// Scales the emission rate, 0 stops it.
+    pub intensity: f32,
+    /// Fraction of a particle left over from earlier frames.
+    carry: f32,
+}
+
+impl Emitter {
+    pub fn new(effect: &'static EffectDef, offset: Vec2, heading: f32) -> Self {
+        Emitter {
+            effect,
+            offset,
+            heading,
+            intensity: 0.,
+            carry: 0.,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Particle {
+    effect: &'static EffectDef,
+    velocity: Vec2,
+    color: Color,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Hidden particles ready to be thrown out.
+pub struct ParticlePool {
+    free: Vec<Entity>,
+    seed: u64,
+}
+
+impl Default for ParticlePool {
+    fn default() -> Self {
+        ParticlePool {
+            free: Vec::new(),
+            seed: get_randu64(),
+        }
+    }
+}
+
+impl ParticlePool {
+    /// Cheap generator, particles do not need the system randomness.
+    fn random(&mut self, (min, max): (f32, f32)) -> f32 {
+        self.seed = self
+            .seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let r = (self.seed >> 40) as f32 / (1u64 << 24) as f32;
+        min + (max - min) * r
+    }
+}
+
+pub fn setup_particles(mut commands: Commands, mut pool: ResMut<ParticlePool>) {
+    // Entities of the previous game were torn down with the rest of the field
+    pool.free.clear();
+    for _ in 0..POOL_SIZE {
+        let particle = commands
+            .spawn_bundle(SpriteBundle {
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(Particle {
+                effect: &IMPACT,
+                velocity: Vec2::ZERO,
+                color: Color::NONE,
+                age: 0.,
+                lifetime: 0.,
+            })
+            .id();
+        pool.free.push(particle);
+    }
+}
+
+/// Starts the particles of bursts sent this frame and of running emitters.
+pub fn emit_particles(
+    time: Res<GameTime>,
+    mut bursts: EventReader<Burst>,
+    mut pool: ResMut<ParticlePool>,
+    mut emitters: Query<(&mut Emitter, &Transform, &Facing, Option<&Velocity>), Without<Particle>>,
+    mut particles: Query<(&mut Particle, &mut Transform, &mut Sprite, &mut Visibility)>,
+) {
+    let mut emit = |burst: &Burst, count: u32| {
+        let effect = burst.effect;
+        for _ in 0..count {
+            let entity = match pool.free.pop() {
+                Some(entity) => entity,
+                None => return,
+            };
+            let (mut particle, mut transform, mut sprite, mut visibility) =
+                match particles.get_mut(entity) {
+                    Ok(item) => item,
+                    Err(_) => continue,
+                };
+            let heading = burst.heading + pool.random((-0.5, 0.5)) * effect.spread;
+            particle.effect = effect;
+            particle.velocity = direction(heading) * pool.random(effect.speed) + burst.velocity;
+            particle.color = burst.color.unwrap_or(effect.colors.0);
+            particle.age = 0.;
+            particle.lifetime = pool.random(effect.lifetime);
+            *transform = Transform::from_translation(burst.position.extend(PARTICLE_Z));
+            sprite.color = particle.color;
+            sprite.custom_size = Some(Vec2::splat(effect.size.0));
+            visibility.is_visible = true;
+        }
+    };
+
+    for burst in bursts.iter() {
+        emit(burst, burst.effect.count);
+    }
+
+    let dt = time.delta_seconds();
+    for (mut emitter, transform, facing, velocity) in &mut emitters {
+        emitter.carry += emitter.effect.count as f32 * emitter.intensity * dt;
+        let count = emitter.carry.floor();
+        emitter.carry -= count;
+        if count < 1. {
+            continue;
+        }
+        let position =
+            transform.translation.truncate() + Vec2::from_angle(facing.0).rotate(emitter.offset);
+        let burst = Burst {
+            effect: emitter.effect,
+            position,
+            heading: facing.0 + emitter.heading,
+            velocity: velocity.map_or(Vec2::ZERO, |v| v.0),
+            color: None,
+        };
+        emit(&burst, count as u32);
+    }
+}
+
+/// Moves, shrinks and fades live particles, returning expired ones to the pool.
+pub fn update_particles(
+    time: Res<GameTime>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite, mut visibility) in &mut particles {
+        if !visibility.is_visible {
+            continue;
+        }
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            visibility.is_visible = false;
+            pool.free.push(entity);
+            continue;
+        }
+
+        let effect = particle.effect;
+        let t = particle.age / particle.lifetime;
+        particle.velocity *= (1. - effect.drag * dt).max(0.);
+        transform.translation += (particle.velocity * dt).extend(0.);
+        sprite.custom_size = Some(Vec2::splat(
+            effect.size.0 + (effect.size.1 - effect.size.0) * t,
+        ));
+        let (from, to) = (particle.color.as_rgba_f32(), effect.colors.1.as_rgba_f32());
+        sprite.color = Color::rgba(
+            from[0] + (to[0] - from[0]) * t,
+            from[1] + (to[1] - from[1]) * t,
+            from[2] + (to[2] - from[2]) * t,
+            from[3] + (to[3] - from[3]) * t,
+        );
+    }
+}
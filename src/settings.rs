@@ -0,0 +1,301 @@
+//! Player preferences, loaded on startup and saved when leaving the settings
+//! screen.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{controls::Bindings, storage};
+
+const SETTINGS_KEY: &str = "settings";
+/// Where key bindings were kept before the other settings joined them.
+const OLD_BINDINGS_KEY: &str = "bindings";
+
+/// How the playfield is fitted into a window of another shape.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scaling {
+    /// Keeps to the playfield, with bars filling the rest of the window.
+    Letterbox,
+    /// Fills the window, showing a bit past the playfield edges.
+    Fit,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+    Standard,
+    /// Colors told apart with the common forms of color blindness.
+    Colorblind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Seconds between two regular enemy spawns.
+    pub fn spawn_interval(self) -> f32 {
+        match self {
+            Difficulty::Easy => 14.,
+            Difficulty::Normal => 10.,
+            Difficulty::Hard => 7.,
+        }
+    }
+
+    /// Factor applied to enemy speeds.
+    pub fn enemy_speed(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.25,
+        }
+    }
+}
+
+/// Travel distance of wrapping bullets.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BulletRange {
+    Short,
+    Medium,
+    Long,
+}
+
+impl BulletRange {
+    /// Distance in playfield widths.
+    pub fn widths(self) -> f32 {
+        match self {
+            BulletRange::Short => 0.5,
+            BulletRange::Medium => 0.8,
+            BulletRange::Long => 1.2,
+        }
+    }
+}
+
+/// Settings screen entries that are picked from a list of values.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Fullscreen,
+    Scaling,
+    ScreenShake,
+    Flashes,
+    HitStop,
+    Palette,
+    Difficulty,
+    BulletWrap,
+    BulletRange,
+    FriendlyFire,
+    EnemyPhysics,
+}
+
+impl Choice {
+    /// Look and accessibility entries.
+    pub const DISPLAY: [Choice; 6] = [
+        Choice::Fullscreen,
+        Choice::Scaling,
+        Choice::ScreenShake,
+        Choice::Flashes,
+        Choice::HitStop,
+        Choice::Palette,
+    ];
+    /// Entries changing how a game plays, taken into account from the next one.
+    pub const GAMEPLAY: [Choice; 5] = [
+        Choice::Difficulty,
+        Choice::BulletWrap,
+        Choice::BulletRange,
+        Choice::FriendlyFire,
+        Choice::EnemyPhysics,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Choice::Fullscreen => "Fullscreen",
+            Choice::Scaling => "Scaling",
+            Choice::ScreenShake => "Screen shake",
+            Choice::Flashes => "Flashes",
+            Choice::HitStop => "Hit-stop",
+            Choice::Palette => "Colors",
+            Choice::Difficulty => "Difficulty",
+            Choice::BulletWrap => "Bullet wrap",
+            Choice::BulletRange => "Bullet range",
+            Choice::FriendlyFire => "Friendly fire",
+            Choice::EnemyPhysics => "Enemy bounces",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Music,
+    Effects,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 2] = [Channel::Music, Channel::Effects];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Channel::Music => "Music",
+            Channel::Effects => "Effects",
+        }
+    }
+}
+
+/// Missing fields fall back to their defaults, so settings saved by older
+/// versions still load.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// From 0 to 1.
+    pub music_volume: f32,
+    /// From 0 to 1.
+    pub effects_volume: f32,
+    pub bindings: Bindings,
+    pub fullscreen: bool,
+    pub scaling: Scaling,
+    pub screen_shake: bool,
+    /// Full screen flashes on big explosions.
+    pub flashes: bool,
+    /// Short freezes on big hits.
+    pub hit_stop: bool,
+    pub palette: Palette,
+    pub difficulty: Difficulty,
+    /// Bullets wrap around the playfield edges instead of leaving it.
+    pub bullet_wrap: bool,
+    /// How far a wrapping bullet travels before it expires.
+    pub bullet_range: BulletRange,
+    /// Enemy bullets damage other enemies too.
+    pub enemy_friendly_fire: bool,
+    /// Enemies bounce off each other instead of passing through.
+    pub enemy_physics: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            music_volume: 0.6,
+            effects_volume: 0.8,
+            bindings: Bindings::default(),
+            fullscreen: false,
+            scaling: Scaling::Letterbox,
+            screen_shake: true,
+            flashes: true,
+            hit_stop: true,
+            palette: Palette::Standard,
+            difficulty: Difficulty::Normal,
+            bullet_wrap: false,
+            bullet_range: BulletRange::Medium,
+            enemy_friendly_fire: false,
+            enemy_physics: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Settings saved by an earlier session, or the defaults.
+    pub fn load() -> Self {
+        if let Some(settings) =
+            storage::load(SETTINGS_KEY).and_then(|s| serde_json::from_str(&s).ok())
+        {
+            return settings;
+        }
+        // Carry over bindings saved on their own by older versions, once
+        let mut settings = Settings::default();
+        if let Some(bindings) =
+            storage::load(OLD_BINDINGS_KEY).and_then(|s| serde_json::from_str(&s).ok())
+        {
+            settings.bindings = bindings;
+            settings.save();
+        }
+        settings
+    }
+
+    pub fn save(&self) {
+        storage::save(SETTINGS_KEY, &serde_json::to_string(self).unwrap());
+    }
+
+    pub fn volume(&self, channel: Channel) -> f32 {
+        match channel {
+            Channel::Music => self.music_volume,
+            Channel::Effects => self.effects_volume,
+        }
+    }
+
+    pub fn set_volume(&mut self, channel: Channel, volume: f32) {
+        let volume = volume.clamp(0., 1.);
+        match channel {
+            Channel::Music => self.music_volume = volume,
+            Channel::Effects => self.effects_volume = volume,
+        }
+    }
+
+    /// Moves `choice` on to its next value, wrapping around.
+    pub fn cycle(&mut self, choice: Choice) {
+        match choice {
+            Choice::Fullscreen => self.fullscreen = !self.fullscreen,
+            Choice::Scaling => {
+                self.scaling = match self.scaling {
+                    Scaling::Letterbox => Scaling::Fit,
+                    Scaling::Fit => Scaling::Letterbox,
+                }
+            }
+            Choice::ScreenShake => self.screen_shake = !self.screen_shake,
+            Choice::Flashes => self.flashes = !self.flashes,
+            Choice::HitStop => self.hit_stop = !self.hit_stop,
+            Choice::Palette => {
+                self.palette = match self.palette {
+                    Palette::Standard => Palette::Colorblind,
+                    Palette::Colorblind => Palette::Standard,
+                }
+            }
+            Choice::Difficulty => {
+                self.difficulty = match self.difficulty {
+                    Difficulty::Easy => Difficulty::Normal,
+                    Difficulty::Normal => Difficulty::Hard,
+                    Difficulty::Hard => Difficulty::Easy,
+                }
+            }
+            Choice::BulletWrap => self.bullet_wrap = !self.bullet_wrap,
+            Choice::BulletRange => {
+                self.bullet_range = match self.bullet_range {
+                    BulletRange::Short => BulletRange::Medium,
+                    BulletRange::Medium => BulletRange::Long,
+                    BulletRange::Long => BulletRange::Short,
+                }
+            }
+            Choice::FriendlyFire => self.enemy_friendly_fire = !self.enemy_friendly_fire,
+            Choice::EnemyPhysics => self.enemy_physics = !self.enemy_physics,
+        }
+    }
+
+    /// Current value of `choice` as shown in the settings screen.
+    pub fn describe(&self, choice: Choice) -> &'static str {
+        let on_off = |on| if on { "On" } else { "Off" };
+        match choice {
+            Choice::Fullscreen => on_off(self.fullscreen),
+            Choice::Scaling => match self.scaling {
+                Scaling::Letterbox => "Letterbox",
+                Scaling::Fit => "Fit",
+            },
+            Choice::ScreenShake => on_off(self.screen_shake),
+            Choice::Flashes => on_off(self.flashes),
+            Choice::HitStop => on_off(self.hit_stop),
+            Choice::Palette => match self.palette {
+                Palette::Standard => "Standard",
+                Palette::Colorblind => "Colorblind",
+            },
+            Choice::Difficulty => match self.difficulty {
+                Difficulty::Easy => "Easy",
+                Difficulty::Normal => "Normal",
+                Difficulty::Hard => "Hard",
+            },
+            Choice::BulletWrap => on_off(self.bullet_wrap),
+            Choice::BulletRange => match self.bullet_range {
+                BulletRange::Short => "Short",
+                BulletRange::Medium => "Medium",
+                BulletRange::Long => "Long",
+            },
+            Choice::FriendlyFire => on_off(self.enemy_friendly_fire),
+            Choice::EnemyPhysics => on_off(self.enemy_physics),
+        }
+    }
+}
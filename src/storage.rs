@@ -0,0 +1,42 @@
+//! Small key-value store that survives restarts: the browser local storage
+//! on the web, files in the working directory on native builds.
+
+pub use backend::{load, save};
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/storage.js")]
+    extern "C" {
+        fn storage_get(key: &str) -> Option<String>;
+        fn storage_set(key: &str, value: &str);
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        storage_get(key)
+    }
+
+    pub fn save(key: &str, value: &str) {
+        storage_set(key, value)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use bevy::prelude::*;
+
+    fn path(key: &str) -> String {
+        format!("ppoid-{key}.json")
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        std::fs::read_to_string(path(key)).ok()
+    }
+
+    pub fn save(key: &str, value: &str) {
+        if let Err(e) = std::fs::write(path(key), value) {
+            warn!("Could not save {key}: {e}");
+        }
+    }
+}
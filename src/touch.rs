@@ -0,0 +1,199 @@
+//! On-screen joystick and fire button for touch screens. They stay hidden
+//! until the first touch, so desktop players never see them.
+//!
+//! Touches starting on the left half of the window grab the joystick, which
+//! centers itself under the finger, touches on the right half fire.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::controls::{Action, Actions};
+
+#[derive(Default)]
+pub struct TouchControls {
+    /// Set once the device has been touched.
+    pub enabled: bool,
+    /// Touch holding the joystick and where it started.
+    stick: Option<(u64, Vec2)>,
+    /// Joystick deflection, at most 1 long.
+    offset: Vec2,
+    /// Touch holding the fire button.
+    fire: Option<u64>,
+    /// Touches down last frame, to tell new ones apart.
+    held: HashSet<u64>,
+}
+
+#[derive(Component, Clone, Copy)]
+pub enum TouchNode {
+    StickBase,
+    StickKnob,
+    Fire,
+}
+
+/// Joystick radius, scaled with the window so it stays thumb sized.
+fn stick_radius(window: &Window) -> f32 {
+    window.width().min(window.height()) * 0.12
+}
+
+pub fn update_touch_controls(
+    windows: Res<Windows>,
+    touches: Res<Touches>,
+    mut controls: ResMut<TouchControls>,
+    mut actions: ResMut<Actions>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let points: HashMap<u64, Vec2> = backend::touch_points(&touches).into_iter().collect();
+    for (&id, &position) in &points {
+        if controls.held.contains(&id) {
+            continue;
+        }
+        controls.enabled = true;
+        if position.x < window.width() / 2. {
+            if controls.stick.is_none() {
+                controls.stick = Some((id, position));
+            }
+        } else if controls.fire.is_none() {
+            controls.fire = Some(id);
+        }
+    }
+    controls.held = points.keys().copied().collect();
+    // Forget touches that were lifted or cancelled
+    if matches!(controls.stick, Some((id, _)) if !points.contains_key(&id)) {
+        controls.stick = None;
+    }
+    if matches!(controls.fire, Some(id) if !points.contains_key(&id)) {
+        controls.fire = None;
+    }
+
+    controls.offset = match controls
+        .stick
+        .and_then(|(id, origin)| points.get(&id).map(|position| *position - origin))
+    {
+        // Touch positions grow upward, like the cursor
+        Some(delta) => (delta / stick_radius(window)).clamp_length_max(1.),
+        None => Vec2::ZERO,
+    };
+    actions.hold(Action::Thrust, controls.offset.y);
+    actions.hold(Action::TurnLeft, -controls.offset.x);
+    actions.hold(Action::TurnRight, controls.offset.x);
+    if controls.fire.is_some() {
+        actions.hold(Action::Fire, 1.);
+    }
+}
+
+pub fn setup_touch_overlay(mut commands: Commands) {
+    let node = |color: Color| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        color: color.into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    };
+
+    commands
+        .spawn_bundle(node(Color::rgba(1., 1., 1., 0.15)))
+        .insert(TouchNode::StickBase)
+        .with_children(|base| {
+            base.spawn_bundle(node(Color::rgba(1., 1., 1., 0.35)))
+                .insert(TouchNode::StickKnob);
+        });
+    commands
+        .spawn_bundle(node(Color::rgba(1., 0.3, 0.3, 0.25)))
+        .insert(TouchNode::Fire);
+}
+
+/// Places the overlay for the current window size and touches.
+pub fn layout_touch_overlay(
+    windows: Res<Windows>,
+    controls: Res<TouchControls>,
+    mut nodes: Query<(&TouchNode, &mut Style, &mut Visibility, &mut UiColor)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let radius = stick_radius(window);
+    let rest = Vec2::splat(2. * radius);
+
+    for (node, mut style, mut visibility, mut color) in &mut nodes {
+        visibility.is_visible = controls.enabled;
+        let (center, size) = match node {
+            TouchNode::StickBase => (
+                controls.stick.map_or(rest, |(_, origin)| origin),
+                2. * radius,
+            ),
+            // Relative to the base
+            TouchNode::StickKnob => (Vec2::splat(radius) * (Vec2::ONE + controls.offset), radius),
+            TouchNode::Fire => {
+                let pressed = controls.fire.is_some();
+                color.0.set_a(if pressed { 0.5 } else { 0.25 });
+                (Vec2::new(window.width() - rest.x, rest.y), 2. * radius)
+            }
+        };
+        style.size = Size::new(Val::Px(size), Val::Px(size));
+        style.position = UiRect {
+            left: Val::Px(center.x - size / 2.),
+            bottom: Val::Px(center.y - size / 2.),
+            ..default()
+        };
+    }
+}
+
+/// Asks for the player name with the browser prompt, as tapping the canvas
+/// never brings up the on-screen keyboard.
+#[cfg(target_arch = "wasm32")]
+pub fn ask_player_name(current: &str) -> Option<String> {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = window)]
+        fn prompt(message: &str, default: &str) -> Option<String>;
+    }
+
+    prompt("Enter your name:", current)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ask_player_name(_current: &str) -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use bevy::prelude::*;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/touch.js")]
+    extern "C" {
+        #[wasm_bindgen(js_name = touch_points)]
+        fn page_touch_points() -> Vec<f64>;
+    }
+
+    /// Fingers on the canvas, by id, in window coordinates. The winit version
+    /// in use sends no touch events on the web, so `Touches` stays empty and
+    /// the page tracks them instead.
+    pub fn touch_points(_touches: &Touches) -> Vec<(u64, Vec2)> {
+        page_touch_points()
+            .chunks_exact(3)
+            .map(|p| (p[0] as u64, Vec2::new(p[1] as f32, p[2] as f32)))
+            .collect()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use bevy::prelude::*;
+
+    /// Fingers on the window, by id, in window coordinates.
+    pub fn touch_points(touches: &Touches) -> Vec<(u64, Vec2)> {
+        touches.iter().map(|t| (t.id(), t.position())).collect()
+    }
+}
@@ -0,0 +1,29 @@
+// Browsers start audio contexts suspended until the page gets a user gesture,
+// which leaves the game silent. Keep track of every context the game creates
+// and resume them on each click, tap or key press until they are running.
+(function () {
+  const AudioContext = window.AudioContext || window.webkitAudioContext;
+  if (!AudioContext) {
+    return;
+  }
+
+  const contexts = [];
+  window.AudioContext = new Proxy(AudioContext, {
+    construct(target, args) {
+      const context = new target(...args);
+      contexts.push(context);
+      return context;
+    },
+  });
+
+  function resume() {
+    for (const context of contexts) {
+      if (context.state !== 'running') {
+        context.resume();
+      }
+    }
+  }
+  for (const event of ['click', 'keydown', 'touchstart', 'pointerdown']) {
+    document.addEventListener(event, resume, { capture: true });
+  }
+})();
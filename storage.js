@@ -0,0 +1,14 @@
+export function storage_get(key) {
+  try {
+    return window.localStorage.getItem(key);
+  } catch (e) {
+    // Storage can be disabled, e.g. in private windows
+    return null;
+  }
+}
+
+export function storage_set(key, value) {
+  try {
+    window.localStorage.setItem(key, value);
+  } catch (e) {}
+}
@@ -0,0 +1,31 @@
+// Touch points for the game canvas. The winit version Bevy uses never reports
+// touches on the web, so they are tracked here and polled every frame.
+const touches = new Map();
+
+function track(event) {
+  if (!(event.target instanceof HTMLCanvasElement)) {
+    return;
+  }
+  const rect = event.target.getBoundingClientRect();
+  for (const touch of event.changedTouches) {
+    if (event.type === 'touchend' || event.type === 'touchcancel') {
+      touches.delete(touch.identifier);
+    } else {
+      // Window coordinates as Bevy uses them, y growing upward
+      touches.set(touch.identifier, [touch.clientX - rect.left, rect.bottom - touch.clientY]);
+    }
+  }
+}
+
+for (const type of ['touchstart', 'touchmove', 'touchend', 'touchcancel']) {
+  document.addEventListener(type, track, { passive: true });
+}
+
+// Flat list of `id, x, y` for every finger on the canvas.
+export function touch_points() {
+  const points = [];
+  for (const [id, [x, y]] of touches) {
+    points.push(id, x, y);
+  }
+  return new Float64Array(points);
+}